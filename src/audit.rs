@@ -0,0 +1,279 @@
+use std::{fmt::{self, Display}, str::FromStr};
+
+use crate::nsi::IndirectString;
+use crate::snapin::{inproc_servers, module_path, normalize_clsid, SNAPINS_KEY};
+use crate::source::RegistrySource;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Risk {
+    Low,
+    Medium,
+    High,
+}
+
+impl Display for Risk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Risk::Low => "low",
+            Risk::Medium => "medium",
+            Risk::High => "high",
+        })
+    }
+}
+
+/// A module reference that could load something other than what was meant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleFinding {
+    pub risk: Risk,
+    /// The snap-in whose registration refers to the module.
+    pub clsid: String,
+    /// Where the reference is: key, value name (empty for the default
+    /// value) and data.
+    pub key: String,
+    pub value: String,
+    pub data: String,
+    pub issue: String,
+}
+
+impl Display for ModuleFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = if self.value.is_empty() { "(Default)" } else { &self.value };
+        write!(f, "{}: {} {} = {:?}: {}", self.risk, self.key, value, self.data, self.issue)
+    }
+}
+
+/// A module a snap-in registration refers to, and the registry value that
+/// names it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleReference {
+    /// The snap-in whose registration refers to the module.
+    pub clsid: String,
+    pub key: String,
+    /// Empty for the default value.
+    pub value: String,
+    pub data: String,
+    /// The module path in `data`, as written.
+    pub path: String,
+    /// Whether it's a per-user in-proc server shadowing the machine-wide
+    /// one, see `MMCServer::is_user_override`.
+    pub user_override: bool,
+}
+
+/// Every module the snap-in registrations in `source` refer to: the DLLs of
+/// their indirect strings, their `ModuleName`, and the in-proc servers of
+/// the snap-in and its About object.
+pub fn module_references(source: &dyn RegistrySource) -> Vec<ModuleReference> {
+    let mut references = Vec::new();
+
+    for clsid in source.subkeys(SNAPINS_KEY).unwrap_or_default() {
+        let key = format!("{}\\{}", SNAPINS_KEY, clsid);
+        let values = source.values(&key).unwrap_or_default();
+        let mut reference = |key: &str, value: &str, data: String, path: String, user_override: bool| {
+            references.push(ModuleReference { clsid: clsid.clone(), key: key.to_string(), value: value.to_string(), data, path, user_override });
+        };
+
+        for (name, data) in &values {
+            let data = data.to_string();
+            if ["NameStringIndirect", "ProviderStringIndirect", "VersionStringIndirect"].iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                if let Ok(indirect) = IndirectString::from_str(&data) {
+                    reference(&key, name, data, indirect.dllpath, false);
+                }
+            } else if name.eq_ignore_ascii_case("ModuleName") {
+                // A managed snap-in's assembly is in its ApplicationBase, see
+                // `MMCSnapIn::module`
+                let path = match values.iter().find(|(n, _)| n.eq_ignore_ascii_case("ApplicationBase")) {
+                    Some((_, base)) => format!("{}\\{}", base.to_string().trim_end_matches('\\'), data),
+                    None => data.clone(),
+                };
+                reference(&key, name, data, path, false);
+            }
+        }
+
+        // The snap-in's own class, and its About object's if that's another
+        let about = values
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case("About"))
+            .and_then(|(_, d)| normalize_clsid(&d.to_string()));
+        let mut classes: Vec<String> = normalize_clsid(&clsid).into_iter().chain(about).collect();
+        classes.dedup();
+
+        for class in classes {
+            let servers = inproc_servers(source, &class);
+            for server in &servers {
+                let server_key = format!("{}\\{}\\InprocServer32", server.root, server.clsid);
+                reference(&server_key, "", server.path.clone(), server.path.clone(), server.is_user_override(&servers));
+            }
+        }
+    }
+
+    references
+}
+
+/// Finds the module references in `source` that are relative, unquoted with
+/// spaces, in a directory users can write to, or per-user overrides. With
+/// `check_files`, also those missing from disk, which only makes sense for
+/// the registry of this machine. Most risky first.
+pub fn audit_modules(source: &dyn RegistrySource, check_files: bool) -> Vec<ModuleFinding> {
+    let mut findings = Vec::new();
+
+    // ModuleName only names the module, nothing loads it from there
+    let references = module_references(source).into_iter().filter(|r| !r.value.eq_ignore_ascii_case("ModuleName"));
+    for reference in references {
+        let mut issues = check_path(&reference.path, check_files);
+        // Writing to the user's own registry is enough to add one
+        if reference.user_override {
+            issues.push((Risk::High, "registered per user, shadowing the machine-wide server".to_string()));
+        }
+        for (risk, issue) in issues {
+            findings.push(ModuleFinding {
+                risk,
+                clsid: reference.clsid.clone(),
+                key: reference.key.clone(),
+                value: reference.value.clone(),
+                data: reference.data.clone(),
+                issue,
+            });
+        }
+    }
+
+    findings.sort_by_key(|f| std::cmp::Reverse(f.risk));
+    findings
+}
+
+fn check_path(path: &str, check_files: bool) -> Vec<(Risk, String)> {
+    let mut issues = Vec::new();
+    let quoted = path.trim().starts_with('"');
+    let unquoted = path.trim().trim_matches('"');
+
+    if !is_absolute(unquoted) {
+        issues.push((Risk::High, "relative path, found through the DLL search order".to_string()));
+    } else if let Some(location) = writable_location(&module_path(unquoted).to_string_lossy()) {
+        issues.push((Risk::High, format!("in {}, which users can usually write to", location)));
+    }
+    if unquoted.contains(' ') && !quoted {
+        issues.push((Risk::Low, "contains spaces but isn't quoted".to_string()));
+    }
+    if check_files && !module_path(unquoted).is_file() {
+        issues.push((Risk::Medium, "not found on disk, so anyone who can create it decides what loads".to_string()));
+    }
+
+    issues
+}
+
+// A drive or UNC path, or one starting with an environment variable, which
+// are all absolute
+fn is_absolute(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    let drive = bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && matches!(bytes[2], b'\\' | b'/');
+    let variable = path.starts_with('%') && path[1..].find('%').is_some_and(|end| {
+        matches!(path.as_bytes().get(end + 2), None | Some(b'\\' | b'/'))
+    });
+
+    drive || path.starts_with(r"\\") || variable
+}
+
+// By default users can write to their profile, the temp directories,
+// ProgramData, and directories created at the root of a drive. This goes by
+// the path only, not the directory's actual permissions.
+fn writable_location(path: &str) -> Option<&'static str> {
+    let path = path.replace('/', "\\").to_lowercase();
+
+    const VARIABLES: [(&str, &str); 7] = [
+        ("%userprofile%", "the user profile"),
+        ("%appdata%", "the user profile"),
+        ("%localappdata%", "the user profile"),
+        ("%temp%", "a temp directory"),
+        ("%tmp%", "a temp directory"),
+        ("%programdata%", "ProgramData"),
+        ("%public%", "the public profile"),
+    ];
+    const DIRECTORIES: [(&str, &str); 4] = [
+        (r"\users\", "the user profiles"),
+        (r"\temp\", "a temp directory"),
+        (r"\tmp\", "a temp directory"),
+        (r"\programdata\", "ProgramData"),
+    ];
+
+    if let Some((_, location)) = VARIABLES.iter().find(|(v, _)| path.starts_with(v)) {
+        return Some(location);
+    }
+    if let Some((_, location)) = DIRECTORIES.iter().find(|(d, _)| path.contains(d)) {
+        return Some(location);
+    }
+
+    // X:\<directory>\...: anything but Windows and Program Files
+    let rest = path.get(3..).filter(|_| path.get(1..3) == Some(r":\"))?;
+    let top = rest.split('\\').next().filter(|_| rest.contains('\\'))?;
+    match top {
+        "windows" | "program files" | "program files (x86)" => None,
+        _ => Some("a directory at the root of the drive"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::RegFile;
+
+    #[test]
+    fn test_is_absolute() {
+        assert!(is_absolute(r"C:\Windows\System32\mmcndmgr.dll"));
+        assert!(is_absolute(r"%SystemRoot%\System32\mmcndmgr.dll"));
+        assert!(is_absolute(r"\\server\share\snapin.dll"));
+        assert!(!is_absolute("mmcndmgr.dll"));
+        assert!(!is_absolute(r"bin\snapin.dll"));
+        assert!(!is_absolute(r"%Name%snapin.dll"));
+    }
+
+    #[test]
+    fn test_writable_location() {
+        assert_eq!(writable_location(r"C:\Users\alice\AppData\Local\snapin.dll"), Some("the user profiles"));
+        assert_eq!(writable_location(r"%TEMP%\snapin.dll"), Some("a temp directory"));
+        assert_eq!(writable_location(r"C:\Tools\snapin.dll"), Some("a directory at the root of the drive"));
+        assert_eq!(writable_location(r"C:\Program Files\Contoso\snapin.dll"), None);
+        assert_eq!(writable_location(r"C:\Windows\System32\snapin.dll"), None);
+        assert_eq!(writable_location(r"C:\snapin.dll"), None);
+    }
+
+    #[test]
+    fn test_audit_modules() {
+        let file = RegFile::parse(r#"Windows Registry Editor Version 5.00
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{11111111-2222-3333-4444-555555555555}]
+"NameStringIndirect"="@contoso.dll,-101"
+"ProviderStringIndirect"="@%SystemRoot%\\System32\\contoso.dll,-102"
+"About"="{11111111-2222-3333-4444-555555555556}"
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Classes\CLSID\{11111111-2222-3333-4444-555555555556}\InprocServer32]
+@="C:\\Program Files\\Contoso\\contoso.dll"
+
+[HKEY_CLASSES_ROOT\CLSID\{11111111-2222-3333-4444-555555555555}\InprocServer32]
+@="\"C:\\Program Files\\Contoso\\contoso.dll\""
+"#).unwrap();
+
+        let findings: Vec<String> = audit_modules(&file, false).iter().map(ToString::to_string).collect();
+        assert_eq!(findings, vec![
+            r#"high: HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{11111111-2222-3333-4444-555555555555} NameStringIndirect = "@contoso.dll,-101": relative path, found through the DLL search order"#,
+            r#"low: HKEY_LOCAL_MACHINE\SOFTWARE\Classes\CLSID\{11111111-2222-3333-4444-555555555556}\InprocServer32 (Default) = "C:\\Program Files\\Contoso\\contoso.dll": contains spaces but isn't quoted"#,
+        ]);
+    }
+
+    #[test]
+    fn test_user_override() {
+        let file = RegFile::parse(r#"Windows Registry Editor Version 5.00
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}]
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Classes\CLSID\{58221C67-EA27-11CF-ADCF-00AA00A80033}\InprocServer32]
+@="%SystemRoot%\\System32\\mycomput.dll"
+
+[HKEY_CURRENT_USER\Software\Classes\CLSID\{58221C67-EA27-11CF-ADCF-00AA00A80033}\InprocServer32]
+@="%SystemRoot%\\System32\\mycomput.dll"
+"#).unwrap();
+
+        let findings: Vec<String> = audit_modules(&file, false).iter().map(ToString::to_string).collect();
+        assert_eq!(findings, vec![
+            r#"high: HKEY_CURRENT_USER\Software\Classes\CLSID\{58221C67-EA27-11CF-ADCF-00AA00A80033}\InprocServer32 (Default) = "%SystemRoot%\\System32\\mycomput.dll": registered per user, shadowing the machine-wide server"#,
+        ]);
+    }
+}
@@ -0,0 +1,568 @@
+use std::{error::Error, fs, ops::Range, path::Path};
+
+use sha1::Sha1;
+use sha2::{digest::DynDigest, Digest, Sha256, Sha384, Sha512};
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const OCTET_STRING: u8 = 0x04;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const BMP_STRING: u8 = 0x1e;
+const CONTEXT_0: u8 = 0xa0;
+const CONTEXT_1: u8 = 0xa1;
+
+const OID_SIGNED_DATA: &str = "1.2.840.113549.1.7.2";
+const OID_SPC_INDIRECT_DATA: &str = "1.3.6.1.4.1.311.2.1.4";
+const OID_COUNTER_SIGNATURE: &str = "1.2.840.113549.1.9.6";
+const OID_SIGNING_TIME: &str = "1.2.840.113549.1.9.5";
+const OID_RFC3161_TIMESTAMP: &str = "1.3.6.1.4.1.311.3.3.1";
+const OID_TST_INFO: &str = "1.2.840.113549.1.9.16.1.4";
+
+const OID_MD5: &str = "1.2.840.113549.2.5";
+const OID_SHA1: &str = "1.3.14.3.2.26";
+const OID_SHA256: &str = "2.16.840.1.101.3.4.2.1";
+const OID_SHA384: &str = "2.16.840.1.101.3.4.2.2";
+const OID_SHA512: &str = "2.16.840.1.101.3.4.2.3";
+
+const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 2;
+
+/// The Authenticode signature embedded in a module. Read, not trusted: the
+/// certificate chain and the signer's signature aren't verified, only that
+/// the digest it signs is the file's own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// Subject of the signing certificate, like `CN=Contoso, O=Contoso Ltd`.
+    pub signer: Option<String>,
+    pub issuer: Option<String>,
+    /// When a timestamping authority countersigned it, in UTC.
+    pub timestamp: Option<String>,
+    pub digest_algorithm: String,
+    /// Whether the signed digest is the file's; `None` for algorithms we
+    /// can't compute.
+    pub digest_matches: Option<bool>,
+}
+
+/// Reads the signature of the module at `path`. See [`parse`].
+pub fn read(path: &Path) -> Result<Option<Signature>, Box<dyn Error>> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(parse(&data)?)
+}
+
+/// The signature in a PE file's certificate table, or `None` when it has
+/// none: the file is unsigned, or signed in a catalog instead.
+pub fn parse(data: &[u8]) -> Result<Option<Signature>, String> {
+    let layout = Layout::parse(data)?;
+    let Some(pkcs7) = layout.signed_data(data)? else { return Ok(None) };
+
+    let fields = signed_data(pkcs7)?;
+    let [_version, _algorithms, content, rest @ ..] = fields.as_slice() else {
+        return Err("truncated signed data".into());
+    };
+    let signer_infos = rest.last().ok_or("signed data has no signer")?.expect(SET)?;
+
+    // What's signed is the file's digest, in an SpcIndirectDataContent
+    let content = content.children()?;
+    if content.first().map(Der::oid).transpose()?.as_deref() != Some(OID_SPC_INDIRECT_DATA) {
+        return Err("signed data isn't Authenticode".into());
+    }
+    let digest_info = content.get(1).ok_or("no signed content")?.expect(CONTEXT_0)?.nth(0)?.nth(1)?;
+    let digest_oid = digest_info.nth(0)?.nth(0)?.oid()?;
+    let digest = digest_info.nth(1)?.expect(OCTET_STRING)?.content;
+
+    let digest_matches = file_digest(&digest_oid).map(|mut file_digest| {
+        for range in layout.hashed_ranges(data.len()) {
+            file_digest.update(&data[range]);
+        }
+        *file_digest.finalize() == *digest
+    });
+
+    let signer_info = signer_infos.nth(0)?;
+    let certificates = rest.iter().find(|d| d.tag == CONTEXT_0);
+    let (signer, issuer) = match certificates {
+        Some(certificates) => find_signer(*certificates, signer_info)?.unzip(),
+        None => (None, None),
+    };
+    let timestamp = match signer_info.children()?.into_iter().find(|d| d.tag == CONTEXT_1) {
+        Some(attributes) => timestamp(attributes)?,
+        None => None,
+    };
+
+    Ok(Some(Signature {
+        signer,
+        issuer,
+        timestamp,
+        digest_algorithm: digest_name(&digest_oid),
+        digest_matches,
+    }))
+}
+
+/// The SHA-1 and SHA-256 Authenticode hashes of a PE file: the digests a
+/// signature signs, which leave the signature out and so don't change when
+/// the file is signed again.
+pub fn image_hashes(data: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let layout = Layout::parse(data)?;
+    let mut sha1 = Sha1::new();
+    let mut sha256 = Sha256::new();
+    for range in layout.hashed_ranges(data.len()) {
+        Digest::update(&mut sha1, &data[range.clone()]);
+        Digest::update(&mut sha256, &data[range]);
+    }
+    Ok((sha1.finalize().to_vec(), sha256.finalize().to_vec()))
+}
+
+// Where the checksum and certificate table are, which the Authenticode digest
+// leaves out
+struct Layout {
+    checksum: usize,
+    security_entry: usize,
+    table: Option<Range<usize>>,
+}
+
+impl Layout {
+    fn parse(data: &[u8]) -> Result<Self, String> {
+        let u16_at = |offset: usize| data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+        let u32_at = |offset: usize| data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+
+        if data.get(..2) != Some(b"MZ") {
+            return Err("not a PE file".into());
+        }
+        let pe = u32_at(0x3c).ok_or("not a PE file")?;
+        if data.get(pe..pe + 4) != Some(b"PE\0\0") {
+            return Err("not a PE file".into());
+        }
+
+        // The optional header follows the COFF header, its data directories
+        // come after fields whose size depends on PE32 or PE32+
+        let optional = pe + 24;
+        let directories = match u16_at(optional) {
+            Some(0x10b) => optional + 96,
+            Some(0x20b) => optional + 112,
+            _ => return Err("unknown optional header".into()),
+        };
+        let count = u32_at(directories - 4).ok_or("truncated optional header")?;
+        let security_entry = directories + 4 * 8;
+
+        let table = match (u32_at(security_entry), u32_at(security_entry + 4)) {
+            (Some(offset), Some(size)) if count > 4 && size > 0 => {
+                if offset < security_entry + 8 || offset.checked_add(size).is_none_or(|end| end > data.len()) {
+                    return Err("certificate table is outside the file".into());
+                }
+                Some(offset..offset + size)
+            }
+            _ => None,
+        };
+
+        Ok(Layout { checksum: optional + 64, security_entry, table })
+    }
+
+    // The PKCS#7 SignedData of the first signature in the certificate table
+    fn signed_data<'a>(&self, data: &'a [u8]) -> Result<Option<&'a [u8]>, String> {
+        let Some(table) = &self.table else { return Ok(None) };
+        let mut rest = &data[table.clone()];
+
+        // WIN_CERTIFICATEs, each aligned to 8 bytes
+        while rest.len() >= 8 {
+            let length = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let kind = u16::from_le_bytes([rest[6], rest[7]]);
+            let certificate = rest.get(8..length).ok_or("truncated certificate table")?;
+            if kind == WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+                return Ok(Some(certificate));
+            }
+            rest = rest.get(length.next_multiple_of(8)..).unwrap_or_default();
+        }
+
+        Ok(None)
+    }
+
+    // Everything but the checksum, the security directory entry and the
+    // certificate table. Hashing the file in order is what signing tools do,
+    // as long as the table is at the end.
+    fn hashed_ranges(&self, len: usize) -> Vec<Range<usize>> {
+        let table = self.table.clone().unwrap_or(len..len);
+        vec![
+            0..self.checksum,
+            self.checksum + 4..self.security_entry,
+            self.security_entry + 8..table.start,
+            table.end..len,
+        ]
+    }
+}
+
+// A DER encoded element
+#[derive(Debug, Clone, Copy)]
+struct Der<'a> {
+    tag: u8,
+    content: &'a [u8],
+    // The whole element, tag and length included
+    raw: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    // The first element in `data`, and what follows it
+    fn read(data: &'a [u8]) -> Result<(Self, &'a [u8]), String> {
+        let (&tag, rest) = data.split_first().ok_or("truncated DER")?;
+        if tag & 0x1f == 0x1f {
+            return Err("multi-byte DER tags aren't supported".into());
+        }
+
+        let (&first, rest) = rest.split_first().ok_or("truncated DER")?;
+        let (length, rest) = match first {
+            0x80 => return Err("indefinite DER lengths aren't supported".into()),
+            n if n < 0x80 => (n as usize, rest),
+            n => {
+                let count = (n & 0x7f) as usize;
+                if count > 4 || rest.len() < count {
+                    return Err("invalid DER length".into());
+                }
+                let length = rest[..count].iter().fold(0, |length, b| length << 8 | *b as usize);
+                (length, &rest[count..])
+            }
+        };
+
+        let content = rest.get(..length).ok_or("DER element is longer than its data")?;
+        let header = data.len() - rest.len();
+        Ok((Der { tag, content, raw: &data[..header + length] }, &rest[length..]))
+    }
+
+    fn children(&self) -> Result<Vec<Der<'a>>, String> {
+        let mut children = Vec::new();
+        let mut rest = self.content;
+        while !rest.is_empty() {
+            let (child, next) = Der::read(rest)?;
+            children.push(child);
+            rest = next;
+        }
+        Ok(children)
+    }
+
+    fn nth(&self, index: usize) -> Result<Der<'a>, String> {
+        self.children()?.get(index).copied().ok_or(format!("DER element {:#04x} has no item {}", self.tag, index))
+    }
+
+    fn expect(self, tag: u8) -> Result<Self, String> {
+        match self.tag == tag {
+            true => Ok(self),
+            false => Err(format!("expected DER tag {:#04x}, found {:#04x}", tag, self.tag)),
+        }
+    }
+
+    // In dotted form, like 1.2.840.113549.1.7.2
+    fn oid(&self) -> Result<String, String> {
+        let content = self.expect(OBJECT_IDENTIFIER)?.content;
+        let mut arcs = Vec::new();
+        let mut value: u64 = 0;
+        for &b in content {
+            if value > u64::MAX >> 7 {
+                return Err("object identifier is too long".into());
+            }
+            value = value << 7 | (b & 0x7f) as u64;
+            if b & 0x80 == 0 {
+                arcs.push(value);
+                value = 0;
+            }
+        }
+
+        // The first byte holds the first two arcs
+        let Some((&first, rest)) = arcs.split_first() else { return Err("empty object identifier".into()) };
+        let (a, b) = match first {
+            0..=79 => (first / 40, first % 40),
+            _ => (2, first - 80),
+        };
+        Ok([a, b].iter().chain(rest).map(u64::to_string).collect::<Vec<_>>().join("."))
+    }
+}
+
+// The fields of the SignedData in a PKCS#7 ContentInfo
+fn signed_data(content_info: &[u8]) -> Result<Vec<Der<'_>>, String> {
+    let (content_info, _) = Der::read(content_info)?;
+    match content_info.expect(SEQUENCE)?.children()?.as_slice() {
+        [kind, content] if kind.oid()? == OID_SIGNED_DATA => content.expect(CONTEXT_0)?.nth(0)?.expect(SEQUENCE)?.children(),
+        _ => Err("not PKCS#7 signed data".into()),
+    }
+}
+
+// The subject and issuer of the certificate whose issuer and serial number
+// the signer info names
+fn find_signer(certificates: Der, signer_info: Der) -> Result<Option<(String, String)>, String> {
+    // Signers named by subject key identifier instead aren't used by Authenticode
+    let id = signer_info.nth(1)?;
+    if id.tag != SEQUENCE {
+        return Ok(None);
+    }
+    let (issuer, serial) = (id.nth(0)?, id.nth(1)?);
+
+    for certificate in certificates.children()? {
+        let mut fields = certificate.nth(0)?.children()?;
+        // The version is optional
+        if fields.first().is_some_and(|f| f.tag == CONTEXT_0) {
+            fields.remove(0);
+        }
+        if let [certificate_serial, _algorithm, certificate_issuer, _validity, subject, ..] = fields.as_slice() {
+            if certificate_serial.content == serial.content && certificate_issuer.raw == issuer.raw {
+                return Ok(Some((name(*subject)?, name(*certificate_issuer)?)));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+// Most specific part first, the way Windows shows certificate names
+fn name(name: Der) -> Result<String, String> {
+    let mut parts = Vec::new();
+    for relative_name in name.expect(SEQUENCE)?.children()? {
+        for attribute in relative_name.children()? {
+            if let [kind, value] = attribute.children()?.as_slice() {
+                let kind = kind.oid()?;
+                let short = match kind.as_str() {
+                    "2.5.4.3" => "CN",
+                    "2.5.4.5" => "SERIALNUMBER",
+                    "2.5.4.6" => "C",
+                    "2.5.4.7" => "L",
+                    "2.5.4.8" => "S",
+                    "2.5.4.10" => "O",
+                    "2.5.4.11" => "OU",
+                    "1.2.840.113549.1.9.1" => "E",
+                    _ => &kind,
+                };
+                parts.push(format!("{}={}", short, text(*value)));
+            }
+        }
+    }
+
+    parts.reverse();
+    Ok(parts.join(", "))
+}
+
+fn text(value: Der) -> String {
+    match value.tag {
+        BMP_STRING => {
+            let wide: Vec<u16> = value.content.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&wide)
+        }
+        _ => String::from_utf8_lossy(value.content).into_owned(),
+    }
+}
+
+// From either kind of countersignature in the signer's unsigned attributes
+fn timestamp(attributes: Der) -> Result<Option<String>, String> {
+    for attribute in attributes.children()? {
+        let [kind, values] = attribute.children()?[..] else { continue };
+        let Some(value) = values.children()?.first().copied() else { continue };
+
+        match kind.oid()?.as_str() {
+            // A SignerInfo, whose signing time is an authenticated attribute
+            OID_COUNTER_SIGNATURE => {
+                let Some(signed) = value.children()?.into_iter().find(|d| d.tag == CONTEXT_0) else { continue };
+                for signed_attribute in signed.children()? {
+                    let [kind, values] = signed_attribute.children()?[..] else { continue };
+                    if kind.oid()? == OID_SIGNING_TIME {
+                        return time(values.nth(0)?).map(Some);
+                    }
+                }
+            }
+            // A SignedData, whose content is a TSTInfo with the time
+            OID_RFC3161_TIMESTAMP => {
+                let fields = signed_data(value.raw)?;
+                let content = fields.get(2).ok_or("truncated timestamp")?.children()?;
+                let [kind, info] = content[..] else { continue };
+                if kind.oid()? != OID_TST_INFO {
+                    continue;
+                }
+                let (info, _) = Der::read(info.expect(CONTEXT_0)?.nth(0)?.expect(OCTET_STRING)?.content)?;
+                if let Some(time_field) = info.children()?.into_iter().find(|d| d.tag == GENERALIZED_TIME) {
+                    return time(time_field).map(Some);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(None)
+}
+
+fn time(value: Der) -> Result<String, String> {
+    let s = std::str::from_utf8(value.content).map_err(|_| "invalid time")?;
+    let digits = match value.tag {
+        // Two digit years are 1950 to 2049
+        UTC_TIME => match s.get(..2).and_then(|y| y.parse::<u32>().ok()) {
+            Some(year) if year >= 50 => format!("19{}", s),
+            Some(_) => format!("20{}", s),
+            None => return Err(format!("invalid time '{}'", s)),
+        },
+        GENERALIZED_TIME => s.to_string(),
+        tag => return Err(format!("expected a time, found DER tag {:#04x}", tag)),
+    };
+
+    let d = digits.get(..14).filter(|d| d.bytes().all(|b| b.is_ascii_digit())).ok_or(format!("invalid time '{}'", s))?;
+    Ok(format!("{}-{}-{} {}:{}:{} UTC", &d[..4], &d[4..6], &d[6..8], &d[8..10], &d[10..12], &d[12..14]))
+}
+
+fn digest_name(oid: &str) -> String {
+    match oid {
+        OID_MD5 => "md5",
+        OID_SHA1 => "sha1",
+        OID_SHA256 => "sha256",
+        OID_SHA384 => "sha384",
+        OID_SHA512 => "sha512",
+        _ => oid,
+    }
+    .to_string()
+}
+
+// Hashes the file with the algorithm `oid` names, if it's one we know
+fn file_digest(oid: &str) -> Option<Box<dyn DynDigest>> {
+    match oid {
+        OID_SHA1 => Some(Box::new(Sha1::new())),
+        OID_SHA256 => Some(Box::new(Sha256::new())),
+        OID_SHA384 => Some(Box::new(Sha384::new())),
+        OID_SHA512 => Some(Box::new(Sha512::new())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn der(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+        let content = parts.concat();
+        let mut out = vec![tag];
+        match content.len() {
+            n if n < 0x80 => out.push(n as u8),
+            n if n < 0x100 => out.extend([0x81, n as u8]),
+            n => out.extend([0x82, (n >> 8) as u8, n as u8]),
+        }
+        out.extend(content);
+        out
+    }
+
+    fn oid(dotted: &str) -> Vec<u8> {
+        let arcs: Vec<u64> = dotted.split('.').map(|a| a.parse().unwrap()).collect();
+        let mut content = Vec::new();
+        for arc in [arcs[0] * 40 + arcs[1]].iter().chain(&arcs[2..]) {
+            let mut bytes = vec![(arc & 0x7f) as u8];
+            let mut rest = arc >> 7;
+            while rest > 0 {
+                bytes.push((rest & 0x7f) as u8 | 0x80);
+                rest >>= 7;
+            }
+            content.extend(bytes.iter().rev());
+        }
+        der(OBJECT_IDENTIFIER, &[&content])
+    }
+
+    fn common_name(name: &str) -> Vec<u8> {
+        let attribute = der(SEQUENCE, &[&oid("2.5.4.3"), &der(0x0c, &[name.as_bytes()])]);
+        der(SEQUENCE, &[&der(SET, &[&attribute])])
+    }
+
+    // A PE32+ header and some bytes standing in for sections
+    fn unsigned_pe() -> Vec<u8> {
+        let mut pe: Vec<u8> = (0..0x200).map(|i| i as u8).collect();
+        pe[..0x40].fill(0);
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c] = 0x40;
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        pe[0x58..0x5a].copy_from_slice(&0x20bu16.to_le_bytes());
+        pe[0xc4..0xc8].copy_from_slice(&16u32.to_le_bytes());
+        pe[0xe8..0xf0].fill(0);
+        pe
+    }
+
+    fn sign(mut pe: Vec<u8>) -> Vec<u8> {
+        // Everything but the checksum and security directory entry
+        let digest = Sha256::new()
+            .chain_update(&pe[..0x98])
+            .chain_update(&pe[0x9c..0xe8])
+            .chain_update(&pe[0xf0..])
+            .finalize();
+
+        let algorithm = der(SEQUENCE, &[&oid(OID_SHA256)]);
+        let digest_info = der(SEQUENCE, &[&algorithm, &der(OCTET_STRING, &[&digest])]);
+        let indirect = der(SEQUENCE, &[&der(SEQUENCE, &[&oid("1.3.6.1.4.1.311.2.1.15")]), &digest_info]);
+        let content = der(SEQUENCE, &[&oid(OID_SPC_INDIRECT_DATA), &der(CONTEXT_0, &[&indirect])]);
+
+        let serial = der(0x02, &[&[0x2a]]);
+        let validity = der(SEQUENCE, &[&der(UTC_TIME, &[b"240101000000Z"]), &der(UTC_TIME, &[b"270101000000Z"])]);
+        let tbs = der(SEQUENCE, &[
+            &der(CONTEXT_0, &[&der(0x02, &[&[2]])]),
+            &serial,
+            &algorithm,
+            &common_name("Contoso CA"),
+            &validity,
+            &common_name("Contoso"),
+        ]);
+        let certificate = der(SEQUENCE, &[&tbs]);
+
+        let signing_time = der(SEQUENCE, &[&oid(OID_SIGNING_TIME), &der(SET, &[&der(UTC_TIME, &[b"240501120000Z"])])]);
+        let countersigner = der(SEQUENCE, &[
+            &der(0x02, &[&[1]]),
+            &der(SEQUENCE, &[&common_name("Contoso Timestamping"), &serial]),
+            &algorithm,
+            &der(CONTEXT_0, &[&signing_time]),
+        ]);
+        let countersignature = der(SEQUENCE, &[&oid(OID_COUNTER_SIGNATURE), &der(SET, &[&countersigner])]);
+        let signer_info = der(SEQUENCE, &[
+            &der(0x02, &[&[1]]),
+            &der(SEQUENCE, &[&common_name("Contoso CA"), &serial]),
+            &algorithm,
+            &der(SEQUENCE, &[&oid("1.2.840.113549.1.1.1")]),
+            &der(OCTET_STRING, &[&[0; 16]]),
+            &der(CONTEXT_1, &[&countersignature]),
+        ]);
+
+        let signed_data = der(SEQUENCE, &[
+            &der(0x02, &[&[1]]),
+            &der(SET, &[&algorithm]),
+            &content,
+            &der(CONTEXT_0, &[&certificate]),
+            &der(SET, &[&signer_info]),
+        ]);
+        let mut pkcs7 = der(SEQUENCE, &[&oid(OID_SIGNED_DATA), &der(CONTEXT_0, &[&signed_data])]);
+
+        pkcs7.resize((pkcs7.len() + 8).next_multiple_of(8) - 8, 0);
+        let length = pkcs7.len() as u32 + 8;
+        let offset = pe.len() as u32;
+        pe.extend(length.to_le_bytes());
+        pe.extend(0x200u16.to_le_bytes());
+        pe.extend(WIN_CERT_TYPE_PKCS_SIGNED_DATA.to_le_bytes());
+        pe.extend(pkcs7);
+        pe[0xe8..0xec].copy_from_slice(&offset.to_le_bytes());
+        pe[0xec..0xf0].copy_from_slice(&length.to_le_bytes());
+        pe
+    }
+
+    #[test]
+    fn test_der() {
+        let (element, rest) = Der::read(&[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02, 0xff]).unwrap();
+        assert_eq!(element.oid().unwrap(), OID_SIGNED_DATA);
+        assert_eq!(rest, [0xff]);
+
+        let long = der(OCTET_STRING, &[&[7; 300]]);
+        assert_eq!(Der::read(&long).unwrap().0.content.len(), 300);
+        assert!(Der::read(&long[..200]).is_err());
+        assert!(Der::read(&[0x30, 0x80, 0x00, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(parse(b"not a module").unwrap_err(), "not a PE file");
+        assert_eq!(parse(&unsigned_pe()), Ok(None));
+
+        let signed = sign(unsigned_pe());
+        assert_eq!(parse(&signed), Ok(Some(Signature {
+            signer: Some("CN=Contoso".into()),
+            issuer: Some("CN=Contoso CA".into()),
+            timestamp: Some("2024-05-01 12:00:00 UTC".into()),
+            digest_algorithm: "sha256".into(),
+            digest_matches: Some(true),
+        })));
+
+        let mut tampered = signed;
+        tampered[0x180] ^= 1;
+        assert_eq!(parse(&tampered).unwrap().unwrap().digest_matches, Some(false));
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+
+use serde::Serialize;
+
+use crate::snapin::{normalize_clsid, MMCExtension, MMCServer, MMCSnapIn};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SnapInChange {
+    pub clsid: String,
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct InventoryDiff {
+    pub added: Vec<SnapInChange>,
+    pub removed: Vec<SnapInChange>,
+    pub modified: Vec<SnapInChange>,
+}
+
+impl InventoryDiff {
+    /// Compares two inventories keyed by CLSID. Added and removed entries
+    /// carry no field changes.
+    pub fn new(old: &[MMCSnapIn], new: &[MMCSnapIn]) -> Self {
+        let old = by_clsid(old);
+        let new = by_clsid(new);
+        let mut diff = InventoryDiff::default();
+
+        for (clsid, snapin) in &old {
+            match new.get(clsid) {
+                None => diff.removed.push(SnapInChange {
+                    clsid: clsid.clone(),
+                    name: snapin.get_name().to_string(),
+                    changes: Vec::new(),
+                }),
+                Some(new_snapin) => {
+                    let changes = compare(snapin, new_snapin);
+                    if !changes.is_empty() {
+                        diff.modified.push(SnapInChange {
+                            clsid: clsid.clone(),
+                            name: new_snapin.get_name().to_string(),
+                            changes,
+                        });
+                    }
+                }
+            }
+        }
+
+        for (clsid, snapin) in &new {
+            if !old.contains_key(clsid) {
+                diff.added.push(SnapInChange {
+                    clsid: clsid.clone(),
+                    name: snapin.get_name().to_string(),
+                    changes: Vec::new(),
+                });
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+impl Display for InventoryDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for snapin in &self.added {
+            writeln!(f, "+ {}  {}", snapin.clsid, snapin.name)?;
+        }
+        for snapin in &self.removed {
+            writeln!(f, "- {}  {}", snapin.clsid, snapin.name)?;
+        }
+        for snapin in &self.modified {
+            writeln!(f, "~ {}  {}", snapin.clsid, snapin.name)?;
+            for change in &snapin.changes {
+                match (&change.old, &change.new) {
+                    (Some(old), Some(new)) => writeln!(f, "    {}: {:?} -> {:?}", change.field, old, new)?,
+                    (None, Some(new)) => writeln!(f, "    {}: + {:?}", change.field, new)?,
+                    (Some(old), None) => writeln!(f, "    {}: - {:?}", change.field, old)?,
+                    (None, None) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn by_clsid(snapins: &[MMCSnapIn]) -> BTreeMap<String, &MMCSnapIn> {
+    snapins
+        .iter()
+        .map(|s| (normalize_clsid(&s.clsid).unwrap_or(s.clsid.clone()), s))
+        .collect()
+}
+
+fn compare(old: &MMCSnapIn, new: &MMCSnapIn) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+
+    let mut field = |name: &str, old: Option<String>, new: Option<String>| {
+        if old != new {
+            changes.push(FieldChange { field: name.to_string(), old, new });
+        }
+    };
+
+    field("namestring", old.namestring.clone(), new.namestring.clone());
+    field("description", old.description.clone(), new.description.clone());
+    field("namestringindirect", old.namestringindirect.clone(), new.namestringindirect.clone());
+    field("standalone", Some(old.standalone.to_string()), Some(new.standalone.to_string()));
+    field("providerstringindirect", old.providerstringindirect.clone(), new.providerstringindirect.clone());
+    field("versionstringindirect", old.versionstringindirect.clone(), new.versionstringindirect.clone());
+    field("application_base", old.application_base.clone(), new.application_base.clone());
+    field("module_name", old.module_name.clone(), new.module_name.clone());
+    field("restriction.blocked", Some(old.restriction.blocked.to_string()), Some(new.restriction.blocked.to_string()));
+    field("restriction.policy", old.restriction.policy.clone(), new.restriction.policy.clone());
+
+    let old_about = old.about.as_ref();
+    let new_about = new.about.as_ref();
+    field("about", old_about.map(|_| "present".to_string()), new_about.map(|_| "present".to_string()));
+    field("about.description", old_about.and_then(|a| a.description.clone()), new_about.and_then(|a| a.description.clone()));
+    field("about.provider", old_about.and_then(|a| a.provider.clone()), new_about.and_then(|a| a.provider.clone()));
+    field("about.version", old_about.and_then(|a| a.version.clone()), new_about.and_then(|a| a.version.clone()));
+
+    let languages: BTreeSet<&String> = old.translations.keys().chain(new.translations.keys()).collect();
+    for language in languages {
+        let old_strings = old.translations.get(language).cloned().unwrap_or_default();
+        let new_strings = new.translations.get(language).cloned().unwrap_or_default();
+        field(&format!("translations.{}.name", language), old_strings.name, new_strings.name);
+        field(&format!("translations.{}.provider", language), old_strings.provider, new_strings.provider);
+        field(&format!("translations.{}.version", language), old_strings.version, new_strings.version);
+    }
+
+    // Node types and extensions are sets: report each member that came or went
+    for node_type in &old.node_types {
+        if !new.node_types.contains(node_type) {
+            field("node_types", Some(node_type.clone()), None);
+        }
+    }
+    for node_type in &new.node_types {
+        if !old.node_types.contains(node_type) {
+            field("node_types", None, Some(node_type.clone()));
+        }
+    }
+
+    for extension in &old.extensions {
+        if !new.extensions.contains(extension) {
+            field("extensions", Some(describe_extension(extension)), None);
+        }
+    }
+    for extension in &new.extensions {
+        if !old.extensions.contains(extension) {
+            field("extensions", None, Some(describe_extension(extension)));
+        }
+    }
+
+    for server in &old.servers {
+        if !new.servers.contains(server) {
+            field("servers", Some(describe_server(server)), None);
+        }
+    }
+    for server in &new.servers {
+        if !old.servers.contains(server) {
+            field("servers", None, Some(describe_server(server)));
+        }
+    }
+
+    changes
+}
+
+fn describe_server(server: &MMCServer) -> String {
+    format!("{} {} in {}", server.clsid, server.path, server.root)
+}
+
+fn describe_extension(extension: &MMCExtension) -> String {
+    format!("{} {} on {}", extension.kind, extension.clsid, extension.node_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapin::MMCSnapInStrings;
+
+    fn test_snapin(clsid: &str, name: &str) -> MMCSnapIn {
+        MMCSnapIn {
+            clsid: clsid.into(),
+            namestring: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_added_removed() {
+        let old = vec![test_snapin("{58221C67-EA27-11CF-ADCF-00AA00A80033}", "Computer Management")];
+        let new = vec![test_snapin("{975797FC-4E2A-11D0-B702-00C04FD8DBF7}", "Event Viewer")];
+        let diff = InventoryDiff::new(&old, &new);
+
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].clsid, "{975797FC-4E2A-11D0-B702-00C04FD8DBF7}");
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].name, "Computer Management");
+        assert!(diff.modified.is_empty());
+    }
+
+    #[test]
+    fn test_diff_clsid_case_insensitive() {
+        let old = vec![test_snapin("{58221c67-ea27-11cf-adcf-00aa00a80033}", "Computer Management")];
+        let new = vec![test_snapin("{58221C67-EA27-11CF-ADCF-00AA00A80033}", "Computer Management")];
+
+        assert!(InventoryDiff::new(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn test_diff_modified() {
+        let old = test_snapin("{58221C67-EA27-11CF-ADCF-00AA00A80033}", "Computer Management");
+        let mut new = old.clone();
+        new.namestring = Some("Computer Management (Local)".into());
+        new.node_types.push("{476E6446-AAFF-11D0-B944-00C04FD8D5B0}".into());
+        new.extensions.push(MMCExtension {
+            node_type: "{476E6446-AAFF-11D0-B944-00C04FD8D5B0}".into(),
+            kind: "NameSpace".into(),
+            clsid: "{975797FC-4E2A-11D0-B702-00C04FD8DBF7}".into(),
+            name: None,
+        });
+        let diff = InventoryDiff::new(&[old], &[new]);
+
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].changes, vec![
+            FieldChange {
+                field: "namestring".into(),
+                old: Some("Computer Management".into()),
+                new: Some("Computer Management (Local)".into()),
+            },
+            FieldChange {
+                field: "node_types".into(),
+                old: None,
+                new: Some("{476E6446-AAFF-11D0-B944-00C04FD8D5B0}".into()),
+            },
+            FieldChange {
+                field: "extensions".into(),
+                old: None,
+                new: Some("NameSpace {975797FC-4E2A-11D0-B702-00C04FD8DBF7} on {476E6446-AAFF-11D0-B944-00C04FD8D5B0}".into()),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_diff_translations() {
+        let old = test_snapin("{58221C67-EA27-11CF-ADCF-00AA00A80033}", "Computer Management");
+        let mut new = old.clone();
+        new.translations.insert("de-DE".into(), MMCSnapInStrings { name: Some("Computerverwaltung".into()), ..Default::default() });
+        let diff = InventoryDiff::new(&[old], &[new]);
+
+        assert_eq!(diff.modified[0].changes, vec![FieldChange {
+            field: "translations.de-DE.name".into(),
+            old: None,
+            new: Some("Computerverwaltung".into()),
+        }]);
+    }
+}
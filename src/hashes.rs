@@ -0,0 +1,145 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::audit::ModuleReference;
+use crate::authenticode;
+use crate::snapin::{module_path, normalize_clsid};
+
+/// The hashes of a module file, taken once however many snap-ins use it.
+/// Hex, upper case like `Get-FileHash` prints them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleHashes {
+    /// Expanded, as the file was read.
+    pub path: String,
+    pub sha256: Option<String>,
+    pub sha1: Option<String>,
+    /// The Authenticode hashes, for PE files only. These are what signatures
+    /// and most allow lists go by.
+    pub authenticode_sha256: Option<String>,
+    pub authenticode_sha1: Option<String>,
+    /// Why the file couldn't be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The snap-ins whose registrations refer to the file.
+    pub snapins: Vec<String>,
+}
+
+/// Hashes each module in `references` once, sorted by path.
+pub fn hash_modules(references: &[ModuleReference]) -> Vec<ModuleHashes> {
+    group_modules(references)
+        .into_iter()
+        .map(|(path, snapins)| {
+            let mut module = ModuleHashes { path, snapins, ..Default::default() };
+            match fs::read(&module.path) {
+                Ok(data) => module.hash(&data),
+                Err(e) => module.error = Some(e.to_string()),
+            }
+            module
+        })
+        .collect()
+}
+
+/// Each module in `references` once, expanded and sorted by path, with the
+/// snap-ins that use it.
+pub fn group_modules(references: &[ModuleReference]) -> Vec<(String, Vec<String>)> {
+    let mut modules: Vec<(String, Vec<String>)> = Vec::new();
+
+    for reference in references {
+        let path = module_path(&reference.path).to_string_lossy().into_owned();
+        let clsid = normalize_clsid(&reference.clsid).unwrap_or(reference.clsid.clone());
+        match modules.iter_mut().find(|(p, _)| p.eq_ignore_ascii_case(&path)) {
+            Some((_, snapins)) if !snapins.contains(&clsid) => snapins.push(clsid),
+            Some(_) => {}
+            None => modules.push((path, vec![clsid])),
+        }
+    }
+
+    modules.sort_by_key(|(p, _)| p.to_lowercase());
+    modules
+}
+
+impl ModuleHashes {
+    fn hash(&mut self, data: &[u8]) {
+        let mut sha1 = Sha1::new();
+        sha1.update(data);
+        self.sha1 = Some(hex(&sha1.finalize()));
+        self.sha256 = Some(hex(&Sha256::digest(data)));
+
+        // Anything else, like a resource-only file, just has the flat hashes
+        if let Ok((sha1, sha256)) = authenticode::image_hashes(data) {
+            self.authenticode_sha1 = Some(hex(&sha1));
+            self.authenticode_sha256 = Some(hex(&sha256));
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::module_references;
+    use crate::source::RegFile;
+
+    fn reference(clsid: &str, path: &str) -> ModuleReference {
+        ModuleReference {
+            clsid: clsid.into(),
+            key: String::new(),
+            value: String::new(),
+            data: path.into(),
+            path: path.into(),
+            user_override: false,
+        }
+    }
+
+    #[test]
+    fn test_hash_modules() {
+        let dir = std::env::temp_dir().join(format!("enum-snapins-hashes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("abc.dll");
+        fs::write(&path, b"abc").unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let missing = dir.join("missing.dll").to_string_lossy().into_owned();
+
+        let modules = hash_modules(&[
+            reference("{58221c67-ea27-11cf-adcf-00aa00a80033}", &path),
+            reference("{975797FC-4E2A-11D0-B702-00C04FD8DBF7}", &missing),
+            reference("{975797FC-4E2A-11D0-B702-00C04FD8DBF7}", &path.to_uppercase()),
+        ]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(modules.len(), 2);
+        assert_eq!(modules[0], ModuleHashes {
+            path,
+            sha256: Some("BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD".into()),
+            sha1: Some("A9993E364706816ABA3E25717850C26C9CD0D89D".into()),
+            authenticode_sha256: None,
+            authenticode_sha1: None,
+            error: None,
+            snapins: vec!["{58221C67-EA27-11CF-ADCF-00AA00A80033}".into(), "{975797FC-4E2A-11D0-B702-00C04FD8DBF7}".into()],
+        });
+        assert!(modules[1].error.is_some());
+        assert_eq!(modules[1].sha256, None);
+    }
+
+    #[test]
+    fn test_managed_module() {
+        let file = RegFile::parse(r#"Windows Registry Editor Version 5.00
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\FX:{b05566ad-fe9c-4363-be05-7a4cbb7cb510}]
+"ApplicationBase"="C:\\Program Files\\Contoso\\"
+"ModuleName"="Contoso.Console.dll"
+"#).unwrap();
+
+        let modules = group_modules(&module_references(&file));
+        assert_eq!(modules, [(
+            r"C:\Program Files\Contoso\Contoso.Console.dll".to_string(),
+            vec!["FX:{b05566ad-fe9c-4363-be05-7a4cbb7cb510}".to_string()],
+        )]);
+    }
+}
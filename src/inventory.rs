@@ -0,0 +1,78 @@
+use std::{error::Error, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashes::ModuleHashes;
+use crate::snapin::{self, MMCSnapIn, NoResolver};
+use crate::source::RegFile;
+use crate::summary::Summary;
+
+/// The snap-ins enumerated on a machine, and the modules they use.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Inventory {
+    pub snapins: Vec<MMCSnapIn>,
+    #[serde(default)]
+    pub modules: Vec<ModuleHashes>,
+}
+
+impl Inventory {
+    /// The modules the snap-in `clsid` uses.
+    pub fn modules_of<'a>(&'a self, clsid: &'a str) -> impl Iterator<Item = &'a ModuleHashes> {
+        let clsid = snapin::normalize_clsid(clsid);
+        self.modules.iter().filter(move |m| clsid.as_ref().is_some_and(|c| m.snapins.contains(c)))
+    }
+}
+
+// What's written: the inventory with its summary, which is recomputed from
+// the snap-ins rather than read back
+#[derive(Serialize)]
+struct Saved<'a> {
+    #[serde(flatten)]
+    inventory: &'a Inventory,
+    summary: Summary,
+}
+
+/// Writes an enumerated inventory as JSON so it can be diffed or browsed
+/// later, possibly on another machine.
+pub fn save(path: &Path, inventory: &Inventory) -> Result<(), Box<dyn Error>> {
+    let saved = Saved { inventory, summary: Summary::new(&inventory.snapins) };
+    fs::write(path, serde_json::to_string_pretty(&saved)?)?;
+    Ok(())
+}
+
+/// Loads an inventory written by `save`, or enumerates the registrations in
+/// a `.reg` export. Indirect strings and About objects in a `.reg` file can't
+/// be resolved, so those snap-ins only have what the registry says, and no
+/// modules are hashed.
+pub fn load(path: &Path) -> Result<Inventory, Box<dyn Error>> {
+    if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("reg")) {
+        let snapins = snapin::get_snapins(&RegFile::read(path)?, &NoResolver, 1, &[])?;
+        return Ok(Inventory { snapins, modules: Vec::new() });
+    }
+
+    // Inventories saved before modules were hashed are just the snap-ins
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if json.is_array() {
+        return Ok(Inventory { snapins: serde_json::from_value(json)?, modules: Vec::new() });
+    }
+    Ok(serde_json::from_value(json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_snapins_only() {
+        let path = std::env::temp_dir().join(format!("enum-snapins-inventory-{}.json", std::process::id()));
+        let snapin = MMCSnapIn { clsid: "{58221C67-EA27-11CF-ADCF-00AA00A80033}".into(), ..Default::default() };
+        fs::write(&path, serde_json::to_string(&[&snapin]).unwrap()).unwrap();
+
+        let inventory = load(&path);
+        fs::remove_file(&path).unwrap();
+
+        let inventory = inventory.unwrap();
+        assert_eq!(inventory.snapins.len(), 1);
+        assert!(inventory.modules.is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use std::{error::Error, path::{Path, PathBuf}};
+
+use sha2::{Digest, Sha256};
+
+use crate::msc::{Console, ConsoleSnapIn};
+use crate::snapin::{module_path, normalize_clsid, MMCSnapIn};
+
+/// Starts programs. Launching goes through this so what would be started
+/// can be checked without starting it.
+pub trait Launcher {
+    fn launch(&self, program: &Path, args: &[String]) -> Result<(), Box<dyn Error>>;
+}
+
+/// Starts programs without waiting for them. On Windows this goes through
+/// the shell, so programs that ask to run elevated, like mmc.exe, get a UAC
+/// prompt instead of failing with ERROR_ELEVATION_REQUIRED.
+pub struct ProcessLauncher;
+
+#[cfg(windows)]
+impl Launcher for ProcessLauncher {
+    fn launch(&self, program: &Path, args: &[String]) -> Result<(), Box<dyn Error>> {
+        use windows::core::{w, HSTRING, PCWSTR};
+        use windows::Win32::UI::Shell::{ShellExecuteExW, SEE_MASK_NOASYNC, SHELLEXECUTEINFOW};
+        use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+        // Only paths are passed, which can't have quotes in them
+        let file = HSTRING::from(program.as_os_str());
+        let parameters = HSTRING::from(args.iter().map(|a| format!("\"{}\"", a)).collect::<Vec<_>>().join(" "));
+        let mut info = SHELLEXECUTEINFOW {
+            cbSize: std::mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+            fMask: SEE_MASK_NOASYNC,
+            lpVerb: w!("open"),
+            lpFile: PCWSTR(file.as_ptr()),
+            lpParameters: PCWSTR(parameters.as_ptr()),
+            nShow: SW_SHOWNORMAL.0,
+            ..Default::default()
+        };
+
+        unsafe { ShellExecuteExW(&mut info) }.map_err(|e| format!("Couldn't start {}: {}", program.display(), e))?;
+        Ok(())
+    }
+}
+
+#[cfg(not(windows))]
+impl Launcher for ProcessLauncher {
+    fn launch(&self, program: &Path, args: &[String]) -> Result<(), Box<dyn Error>> {
+        std::process::Command::new(program)
+            .args(args)
+            .spawn()
+            .map_err(|e| format!("Couldn't start {}: {}", program.display(), e))?;
+        Ok(())
+    }
+}
+
+/// Writes a console with `snapins` in it, every extension enabled, and
+/// opens it in MMC. The console is left for MMC to read as
+/// `%TEMP%\enum-snapins\<name>.msc`, named after the snap-in's CLSID or a
+/// hash of several, so opening the same snap-ins again reuses the file
+/// instead of adding another. Its path is returned.
+pub fn open_in_mmc(snapins: &[&MMCSnapIn], launcher: &dyn Launcher) -> Result<PathBuf, Box<dyn Error>> {
+    if snapins.is_empty() {
+        return Err("No snap-ins to open".into());
+    }
+
+    let console = Console::new(snapins.iter().map(|s| ConsoleSnapIn::new(s, None)).collect());
+    let dir = std::env::temp_dir().join("enum-snapins");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.msc", console_name(snapins)));
+    console.write_to(&path)?;
+
+    launcher.launch(&module_path("mmc.exe"), &[path.to_string_lossy().into_owned()])?;
+    Ok(path)
+}
+
+// The CLSID of a single snap-in, otherwise a short hash of all of them
+fn console_name(snapins: &[&MMCSnapIn]) -> String {
+    let mut clsids: Vec<String> = snapins.iter().map(|s| normalize_clsid(&s.clsid).unwrap_or(s.clsid.clone())).collect();
+    clsids.sort();
+    clsids.dedup();
+    if let [clsid] = &clsids[..] {
+        return clsid.clone();
+    }
+
+    let hash = Sha256::digest(clsids.join(",").as_bytes());
+    hash[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    use crate::msc::ConsoleFile;
+
+    // Remembers what it was asked to start
+    #[derive(Default)]
+    struct RecordingLauncher {
+        launched: RefCell<Vec<(PathBuf, Vec<String>)>>,
+    }
+
+    impl Launcher for RecordingLauncher {
+        fn launch(&self, program: &Path, args: &[String]) -> Result<(), Box<dyn Error>> {
+            self.launched.borrow_mut().push((program.to_path_buf(), args.to_vec()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_open_in_mmc() {
+        let snapins = [
+            MMCSnapIn { clsid: "{58221c67-ea27-11cf-adcf-00aa00a80033}".into(), namestring: Some("Computer Management".into()), ..Default::default() },
+            MMCSnapIn { clsid: "{975797FC-4E2A-11D0-B702-00C04FD8DBF7}".into(), namestring: Some("Event Viewer".into()), ..Default::default() },
+        ];
+        let launcher = RecordingLauncher::default();
+
+        let path = open_in_mmc(&[&snapins[0], &snapins[1]], &launcher).unwrap();
+        let console = ConsoleFile::read(&path);
+
+        // The same snap-ins in another order reuse the file
+        assert_eq!(open_in_mmc(&[&snapins[1], &snapins[0]], &launcher).unwrap(), path);
+        std::fs::remove_file(&path).unwrap();
+
+        let launched = launcher.launched.borrow();
+        assert_eq!(launched.len(), 2);
+        assert!(launched[0].0.to_string_lossy().to_lowercase().ends_with(r"\system32\mmc.exe"));
+        assert_eq!(launched[0].1, [path.to_string_lossy().into_owned()]);
+        assert!(path.extension().is_some_and(|e| e == "msc"));
+
+        // Under the Console Root folder
+        let clsids: Vec<String> = console.unwrap().snapins.into_iter().map(|s| s.clsid).collect();
+        assert_eq!(clsids, ["{C96401CC-0E17-11D3-885B-00C04F72C717}", "{58221C67-EA27-11CF-ADCF-00AA00A80033}", "{975797FC-4E2A-11D0-B702-00C04FD8DBF7}"]);
+    }
+
+    #[test]
+    fn test_console_name() {
+        let snapin = MMCSnapIn { clsid: "{58221c67-ea27-11cf-adcf-00aa00a80033}".into(), ..Default::default() };
+        let other = MMCSnapIn { clsid: "{975797FC-4E2A-11D0-B702-00C04FD8DBF7}".into(), ..Default::default() };
+
+        assert_eq!(console_name(&[&snapin]), "{58221C67-EA27-11CF-ADCF-00AA00A80033}");
+        assert_eq!(console_name(&[&snapin, &snapin]), "{58221C67-EA27-11CF-ADCF-00AA00A80033}");
+        assert_eq!(console_name(&[&snapin, &other]).len(), 16);
+    }
+
+    #[test]
+    fn test_nothing_to_open() {
+        let launcher = RecordingLauncher::default();
+        assert!(open_in_mmc(&[], &launcher).is_err());
+        assert!(launcher.launched.borrow().is_empty());
+    }
+}
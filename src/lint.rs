@@ -0,0 +1,243 @@
+use std::{fmt::{self, Display}, str::FromStr};
+
+use crate::nsi::IndirectString;
+use crate::register::{SnapInRegistration, EXTENSION_KINDS};
+use crate::snapin::{inproc_server, normalize_clsid, NODETYPES_KEY, SNAPINS_KEY};
+use crate::source::{RegFile, RegistrySource};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The registration is broken and MMC won't load it as intended.
+    Error,
+    /// Worth knowing, but often fine, like extending a node type no
+    /// snap-in lists, which snap-ins aren't required to.
+    Info,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// The key or description field the finding is about.
+    pub location: String,
+    pub message: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Info => "info",
+        };
+        write!(f, "{}: {}: {}", severity, self.location, self.message)
+    }
+}
+
+#[derive(Default)]
+struct Findings(Vec<Finding>);
+
+impl Findings {
+    fn error(&mut self, location: &str, message: String) {
+        self.0.push(Finding { severity: Severity::Error, location: location.to_string(), message });
+    }
+
+    fn info(&mut self, location: &str, message: String) {
+        self.0.push(Finding { severity: Severity::Info, location: location.to_string(), message });
+    }
+
+    fn clsid(&mut self, location: &str, s: &str) -> Option<String> {
+        let clsid = normalize_clsid(s);
+        if clsid.is_none() {
+            self.error(location, format!("'{}' is not a valid GUID", s));
+        }
+        clsid
+    }
+
+    fn indirect(&mut self, location: &str, s: &str) {
+        if let Err(e) = IndirectString::from_str(s) {
+            self.error(location, format!("'{}' is not an indirect string: {}", s, e));
+        }
+    }
+}
+
+/// Checks the snap-in registrations in `source`. Classes and node types the
+/// registrations refer to may be registered in `source` or in `reference`,
+/// usually the registry of the machine they're meant for.
+pub fn lint_registry(source: &dyn RegistrySource, reference: Option<&dyn RegistrySource>) -> Vec<Finding> {
+    let mut findings = Findings::default();
+    let sources: Vec<&dyn RegistrySource> = [Some(source), reference].into_iter().flatten().collect();
+
+    let mut node_types = Vec::new();
+    for source in &sources {
+        for clsid in source.subkeys(SNAPINS_KEY).unwrap_or_default() {
+            for node_type in source.subkeys(&format!("{}\\{}\\NodeTypes", SNAPINS_KEY, clsid)).unwrap_or_default() {
+                node_types.extend(normalize_clsid(&node_type));
+            }
+        }
+    }
+
+    for clsid in source.subkeys(SNAPINS_KEY).unwrap_or_default() {
+        let key = format!("{}\\{}", SNAPINS_KEY, clsid);
+        findings.clsid(&key, &clsid);
+
+        let values = source.values(&key).unwrap_or_default();
+        let value = |name: &str| values.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, d)| d.to_string());
+
+        if value("NameString").is_none() && value("NameStringIndirect").is_none() {
+            findings.error(&key, "neither NameString nor NameStringIndirect is set".to_string());
+        }
+        for name in ["NameStringIndirect", "ProviderStringIndirect", "VersionStringIndirect"] {
+            if let Some(data) = value(name) {
+                findings.indirect(&format!("{}\\{}", key, name), &data);
+            }
+        }
+        if let Some(about) = value("About") {
+            let location = format!("{}\\About", key);
+            if let Some(about) = findings.clsid(&location, &about) {
+                if !sources.iter().any(|s| inproc_server(*s, &about).is_ok()) {
+                    findings.error(&location, format!("About class {} is not registered", about));
+                }
+            }
+        }
+        for node_type in source.subkeys(&format!("{}\\NodeTypes", key)).unwrap_or_default() {
+            findings.clsid(&format!("{}\\NodeTypes\\{}", key, node_type), &node_type);
+        }
+    }
+
+    for node_type in source.subkeys(NODETYPES_KEY).unwrap_or_default() {
+        let key = format!("{}\\{}", NODETYPES_KEY, node_type);
+        let Some(normalized) = findings.clsid(&key, &node_type) else { continue };
+
+        let extensions_key = format!("{}\\Extensions", key);
+        let kinds = source.subkeys(&extensions_key).unwrap_or_default();
+        if !kinds.is_empty() && !node_types.contains(&normalized) {
+            findings.info(&key, format!("no snap-in lists node type {} under its NodeTypes", normalized));
+        }
+        for kind in kinds {
+            let kind_key = format!("{}\\{}", extensions_key, kind);
+            if !EXTENSION_KINDS.iter().any(|k| k.eq_ignore_ascii_case(&kind)) {
+                findings.error(&kind_key, format!("unknown extension kind '{}'", kind));
+            }
+            for (clsid, _) in source.values(&kind_key).unwrap_or_default() {
+                findings.clsid(&format!("{}\\{}", kind_key, clsid), &clsid);
+            }
+        }
+    }
+
+    findings.0
+}
+
+/// Checks a registration description, then the registration it generates.
+pub fn lint_description(json: &str, reference: Option<&dyn RegistrySource>) -> Vec<Finding> {
+    let mut findings = Findings::default();
+    let registration = match SnapInRegistration::parse(json) {
+        Ok(registration) => registration,
+        Err(e) => {
+            findings.error("description", e);
+            return findings.0;
+        }
+    };
+
+    findings.clsid("clsid", &registration.clsid);
+    if let Some(about) = &registration.about {
+        findings.clsid("about", about);
+    }
+    if registration.name_string.is_none() && registration.name_string_indirect.is_none() {
+        findings.error("description", "neither name_string nor name_string_indirect is set".to_string());
+    }
+    for (field, indirect) in [
+        ("name_string_indirect", &registration.name_string_indirect),
+        ("provider_string_indirect", &registration.provider_string_indirect),
+        ("version_string_indirect", &registration.version_string_indirect),
+    ] {
+        if let Some(indirect) = indirect {
+            findings.indirect(field, indirect);
+        }
+    }
+    for (i, node_type) in registration.node_types.iter().enumerate() {
+        findings.clsid(&format!("node_types[{}]", i), node_type);
+    }
+    for (i, extension) in registration.extensions.iter().enumerate() {
+        findings.clsid(&format!("extensions[{}].node_type", i), &extension.node_type);
+        if !EXTENSION_KINDS.iter().any(|k| k.eq_ignore_ascii_case(&extension.kind)) {
+            findings.error(&format!("extensions[{}].kind", i), format!("unknown extension kind '{}'", extension.kind));
+        }
+    }
+
+    // What's left, like unknown node types, is easier to check in the
+    // registration itself
+    if findings.0.is_empty() {
+        match registration.to_reg().and_then(|reg| RegFile::parse(&reg)) {
+            Ok(file) => findings.0.extend(lint_registry(&file, reference)),
+            Err(e) => findings.error("description", e),
+        }
+    }
+
+    findings.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(findings: &[Finding]) -> Vec<String> {
+        findings.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_lint_registry() {
+        let file = RegFile::parse(r#"Windows Registry Editor Version 5.00
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{11111111-2222-3333-4444-555555555555}]
+"NameStringIndirect"="contoso.dll,-101"
+"About"="{11111111-2222-3333-4444-555555555556}"
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\not-a-guid]
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\NodeTypes\{476E6449-AAFF-11D0-B944-00C04FD8D5B0}\Extensions\Sidebar]
+"{11111111-2222-3333-4444-555555555555}"="Contoso"
+"#).unwrap();
+
+        assert_eq!(messages(&lint_registry(&file, None)), vec![
+            r"error: HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\not-a-guid: 'not-a-guid' is not a valid GUID",
+            r"error: HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\not-a-guid: neither NameString nor NameStringIndirect is set",
+            r"error: HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{11111111-2222-3333-4444-555555555555}\NameStringIndirect: 'contoso.dll,-101' is not an indirect string: String does not start with '@'",
+            r"error: HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{11111111-2222-3333-4444-555555555555}\About: About class {11111111-2222-3333-4444-555555555556} is not registered",
+            r"info: HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\NodeTypes\{476E6449-AAFF-11D0-B944-00C04FD8D5B0}: no snap-in lists node type {476E6449-AAFF-11D0-B944-00C04FD8D5B0} under its NodeTypes",
+            r"error: HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\NodeTypes\{476E6449-AAFF-11D0-B944-00C04FD8D5B0}\Extensions\Sidebar: unknown extension kind 'Sidebar'",
+        ]);
+    }
+
+    #[test]
+    fn test_node_type_from_reference() {
+        let description = r#"{
+            "clsid": "{11111111-2222-3333-4444-555555555555}",
+            "server": "contoso.dll",
+            "name_string": "Contoso",
+            "extensions": [{ "node_type": "{476E6449-AAFF-11D0-B944-00C04FD8D5B0}", "kind": "NameSpace" }]
+        }"#;
+        assert_eq!(lint_description(description, None).len(), 1);
+
+        let reference = RegFile::parse(r#"Windows Registry Editor Version 5.00
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}\NodeTypes\{476E6449-AAFF-11D0-B944-00C04FD8D5B0}]
+"#).unwrap();
+        assert!(lint_description(description, Some(&reference)).is_empty());
+    }
+
+    #[test]
+    fn test_lint_description() {
+        let description = r#"{
+            "clsid": "{11111111-2222-3333-4444-55555555555}",
+            "server": "contoso.dll",
+            "version_string_indirect": "@contoso.dll",
+            "extensions": [{ "node_type": "{476E6449-AAFF-11D0-B944-00C04FD8D5B0}", "kind": "Sidebar" }]
+        }"#;
+
+        assert_eq!(messages(&lint_description(description, None)), vec![
+            "error: clsid: '{11111111-2222-3333-4444-55555555555}' is not a valid GUID",
+            "error: description: neither name_string nor name_string_indirect is set",
+            "error: version_string_indirect: '@contoso.dll' is not an indirect string: Invalid format, expected ','",
+            "error: extensions[0].kind: unknown extension kind 'Sidebar'",
+        ]);
+    }
+}
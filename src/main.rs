@@ -1,23 +1,192 @@
-use std::error::Error;
-use registry::{Hive, Security};
+use std::{error::Error, fs, path::{Path, PathBuf}, sync::OnceLock, time::Duration};
+#[cfg(windows)]
 use window::MyWindow;
+#[cfg(windows)]
 use windows::Win32::System::Com::CoInitialize;
 
+mod audit;
+mod authenticode;
+mod diff;
+mod hashes;
+mod inventory;
+mod launch;
+mod lint;
+mod msc;
 mod nsi;
+mod policy;
+mod query;
+// Only read by the safe resolver, which only resolves the live registry
+#[cfg_attr(not(windows), allow(dead_code))]
+mod pe;
+mod register;
+mod report;
 mod snapin;
+mod source;
+mod summary;
+mod tui;
+mod view;
+mod watch;
+#[cfg(windows)]
 mod window;
 
 use snapin::MMCSnapIn;
 
-fn main() -> Result<(), Box<dyn Error>> {
-    simple_logger::SimpleLogger::new().with_level(log::LevelFilter::max()).init().unwrap();
+/// Options that apply to every command that enumerates the registry.
+#[derive(Default)]
+struct Options {
+    // Worker threads, --jobs <n>
+    jobs: Option<usize>,
+    // Probe About objects in a child process with this timeout, --isolate
+    // [--about-timeout <seconds>]
+    isolate: Option<Duration>,
+    // Never load snap-in modules, only read their files, --safe
+    safe: bool,
+    // Resolve everything again instead of using the cache, --no-cache
+    no_cache: bool,
+    // Also resolve indirect strings in these languages, --languages <a,b,...>
+    languages: Vec<String>,
+    // Only the snap-ins matching this query, --where <query>
+    filter: Option<query::Query>,
+    // Log more than warnings, -v for info, -vv debug, -vvv trace
+    verbose: usize,
+}
+
+static OPTIONS: OnceLock<Options> = OnceLock::new();
+
+// How often to look for registry changes without notifications
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+fn main() {
+    // Shown as they read, since some, like query errors, span lines
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let mut args: Vec<String> = std::env::args().collect();
+    let options = take_options(&mut args)?;
+
+    // Logs go to stderr, leaving stdout to what commands print
+    let level = match options.verbose {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    simple_logger::SimpleLogger::new().with_level(level).init().unwrap();
+    #[cfg(windows)]
     let _ = unsafe { CoInitialize(None) };
 
-    let snapins = get_snapins()?;
+    let _ = OPTIONS.set(options);
+
+    match args.get(1).map(String::as_str) {
+        Some("msc") => return generate_console(&args[2..]),
+        Some("open") => return open_console(&args[2..]),
+        Some("scan-msc") => return scan_consoles(&args[2..]),
+        Some("export") => return export_inventory(&args[2..]),
+        Some("diff") => return diff_inventories(&args[2..]),
+        Some("register") => return write_registration(&args[2..]),
+        Some("lint") => return lint_registrations(&args[2..]),
+        Some("audit") => return audit_modules(&args[2..]),
+        Some("signatures") => return check_signatures(&args[2..]),
+        Some("hashes") => return list_hashes(&args[2..]),
+        Some("policy") => return check_policy(&args[2..]),
+        Some("report") => return write_report(&args[2..]),
+        Some("summary") => return summarize(&args[2..]),
+        Some("query") => return run_query(&args[2..]),
+        Some("tui") => return run_tui(&args[2..]),
+        Some("watch") => return watch_snapins(&args[2..]),
+        Some("about-worker") => return probe_about(&args[2..]),
+        _ => {}
+    }
+
+    run_window()
+}
+
+// Removes the global options from `args`
+fn take_options(args: &mut Vec<String>) -> Result<Options, Box<dyn Error>> {
+    let mut options = Options::default();
+    let mut about_timeout = Duration::from_secs(10);
+    let mut isolate = false;
+
+    let mut i = 0;
+    while i < args.len() {
+        let number = |name: &str| -> Result<u64, Box<dyn Error>> {
+            args.get(i + 1)
+                .and_then(|n| n.parse::<u64>().ok())
+                .filter(|&n| n > 0)
+                .ok_or_else(|| format!("{} needs a positive number", name).into())
+        };
 
-    let standalone_snapins: Vec<MMCSnapIn> = snapins.into_iter().filter(|s| s.standalone).collect();
+        match args[i].as_str() {
+            "--jobs" | "-j" => {
+                options.jobs = Some(number("--jobs")? as usize);
+                args.drain(i..i + 2);
+            }
+            "--about-timeout" => {
+                about_timeout = Duration::from_secs(number("--about-timeout")?);
+                args.drain(i..i + 2);
+            }
+            "--isolate" => {
+                isolate = true;
+                args.remove(i);
+            }
+            "--safe" => {
+                options.safe = true;
+                args.remove(i);
+            }
+            "--languages" => {
+                let languages = args.get(i + 1).ok_or("--languages needs a list of languages, e.g. de-DE,fr-FR")?;
+                options.languages = languages.split(',').map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+                args.drain(i..i + 2);
+            }
+            "--no-cache" => {
+                options.no_cache = true;
+                args.remove(i);
+            }
+            "--verbose" | "-v" | "-vv" | "-vvv" => {
+                options.verbose += match args[i].as_str() {
+                    "-vv" => 2,
+                    "-vvv" => 3,
+                    _ => 1,
+                };
+                args.remove(i);
+            }
+            "--where" => {
+                let filter = args.get(i + 1).ok_or("--where needs a query, e.g. \"standalone && !about\"")?;
+                options.filter = Some(parse_query(filter)?);
+                args.drain(i..i + 2);
+            }
+            _ => i += 1,
+        }
+    }
+
+    options.isolate = isolate.then_some(about_timeout);
+    Ok(options)
+}
 
-    let my = MyWindow::new(standalone_snapins);
+#[cfg(windows)]
+fn run_window() -> Result<(), Box<dyn Error>> {
+    // The window shows About images, which can't come from the cache
+    let snapins = get_live_snapins(true)?;
+    let seen: Vec<MMCSnapIn> = snapins.iter().map(MMCSnapIn::text_only).collect();
+
+    let my = MyWindow::new(snapins);
+    my.watch(move |updates| {
+        let (resolver, _) = live_resolver()?;
+        let languages = &OPTIONS.get_or_init(Options::default).languages;
+        let mut watcher = watch::Watcher::new(&source::LiveRegistry, &resolver, languages, &seen);
+        let mut trigger = watch::Trigger::live(WATCH_INTERVAL);
+        loop {
+            trigger.wait();
+            let update = watcher.refresh(&source::LiveRegistry);
+            if !update.diff.is_empty() {
+                updates.send(update)?;
+            }
+        }
+    });
 
     if let Err(e) = my.wnd.run_main(None) {
         eprintln!("{}", e);
@@ -28,29 +197,606 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn get_snapins() -> Result<Vec<MMCSnapIn>, Box<dyn Error>> {
-    let mut found_snapins: Vec<MMCSnapIn> = Vec::new();
+#[cfg(not(windows))]
+fn run_window() -> Result<(), Box<dyn Error>> {
+    Err("The snap-in window is only available on Windows".into())
+}
 
-    // Open HKLM\SOFTWARE\Microsoft\MMC\SnapIns
-    let snapins = Hive::LocalMachine.open(
-        r"SOFTWARE\Microsoft\MMC\SnapIns",
-        Security::Read
-    )?;
+// enum-snapins msc <config> <output.msc>
+fn generate_console(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (config_path, output_path) = match args {
+        [config, output] => (config, output),
+        _ => return Err("Usage: enum-snapins msc <config> <output.msc>".into()),
+    };
 
-    // Iterate over each subkey in the SnapIns registry key
-    for snapin_key in snapins.keys() {
-        let snapin_key = snapin_key?;
-        let snapin_clsid = snapin_key.to_string();
+    let selections = msc::parse_config(&fs::read_to_string(config_path)?)?;
+    let snapins = get_snapins()?;
+    let console = msc::Console::from_selections(&selections, &snapins)?;
+    console.write_to(Path::new(output_path))?;
 
-        match MMCSnapIn::try_from(snapin_clsid) {
-            Ok(snapin) => {
-                found_snapins.push(snapin);
-            }
+    Ok(())
+}
+
+// enum-snapins open <clsid> [<clsid> ...]
+fn open_console(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        return Err("Usage: enum-snapins open <clsid> [<clsid> ...]".into());
+    }
+
+    let snapins = get_snapins()?;
+    let mut selected = Vec::new();
+    for clsid in args {
+        let clsid = snapin::normalize_clsid(clsid).ok_or(format!("invalid CLSID '{}'", clsid))?;
+        let snapin = snapins
+            .iter()
+            .find(|s| snapin::normalize_clsid(&s.clsid).as_deref() == Some(clsid.as_str()))
+            .ok_or(format!("Snap-in {} is not registered", clsid))?;
+        selected.push(snapin);
+    }
+
+    let path = launch::open_in_mmc(&selected, &launch::ProcessLauncher)?;
+    println!("Opened {}", path.display());
+    Ok(())
+}
+
+// enum-snapins scan-msc <file.msc|directory>...
+fn scan_consoles(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        return Err("Usage: enum-snapins scan-msc <file.msc|directory>...".into());
+    }
+
+    let mut paths = Vec::new();
+    for arg in args {
+        find_consoles(Path::new(arg), &mut paths)?;
+    }
+
+    let snapins = get_snapins()?;
+    let mut unregistered_consoles = 0;
+
+    for path in paths {
+        println!("{}", path.display());
+        let console = match msc::ConsoleFile::read(&path) {
+            Ok(console) => console,
             Err(e) => {
-                eprintln!("{}", e);
+                println!("  error: {}", e);
+                continue;
             }
+        };
+
+        for selection in &console.snapins {
+            let name = snapins
+                .iter()
+                .find(|s| snapin::normalize_clsid(&s.clsid).as_deref() == Some(selection.clsid.as_str()))
+                .map(|s| s.get_name())
+                .unwrap_or("UNREGISTERED");
+            let extensions = match &selection.extensions {
+                None => "all".to_string(),
+                Some(exts) if exts.is_empty() => "none".to_string(),
+                Some(exts) => exts.join(", "),
+            };
+            println!("  {}  {}", selection.clsid, name);
+            println!("    extensions: {}", extensions);
         }
+
+        for node in &console.nodes {
+            println!("  node {}: {} ({})", node.id, node.name.as_deref().unwrap_or(""), node.clsid);
+        }
+
+        let unregistered = console.unregistered(&snapins);
+        if !unregistered.is_empty() {
+            unregistered_consoles += 1;
+            println!("  WARNING: references unregistered snap-ins: {}", unregistered.join(", "));
+        }
+    }
+
+    if unregistered_consoles > 0 {
+        return Err(format!("{} console(s) reference unregistered snap-ins", unregistered_consoles).into());
+    }
+
+    Ok(())
+}
+
+// enum-snapins register <description.json> <output.reg>
+fn write_registration(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (description_path, output_path) = match args {
+        [description, output] => (description, output),
+        _ => return Err("Usage: enum-snapins register <description.json> <output.reg>".into()),
+    };
+
+    let registration = register::SnapInRegistration::parse(&fs::read_to_string(description_path)?)
+        .map_err(|e| format!("{}: {}", description_path, e))?;
+    fs::write(output_path, registration.to_reg_file()?)?;
+
+    Ok(())
+}
+
+// enum-snapins lint <description.json|file.reg>...
+fn lint_registrations(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        return Err("Usage: enum-snapins lint <description.json|file.reg>...".into());
+    }
+
+    // Whatever the registrations refer to may already be on this machine
+    #[cfg(windows)]
+    let reference: Option<&dyn source::RegistrySource> = Some(&source::LiveRegistry);
+    #[cfg(not(windows))]
+    let reference: Option<&dyn source::RegistrySource> = None;
+
+    let mut errors = 0;
+    for arg in args {
+        let path = Path::new(arg);
+        let findings = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("reg")) {
+            lint::lint_registry(&source::RegFile::read(path)?, reference)
+        } else {
+            lint::lint_description(&fs::read_to_string(path)?, reference)
+        };
+
+        for finding in &findings {
+            println!("{}: {}", arg, finding);
+        }
+        errors += findings.iter().filter(|f| f.severity == lint::Severity::Error).count();
+    }
+
+    if errors > 0 {
+        return Err(format!("{} error(s)", errors).into());
+    }
+
+    Ok(())
+}
+
+// enum-snapins audit [file.reg]
+fn audit_modules(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let findings = match args {
+        [] => audit_live()?,
+        [path] => audit::audit_modules(&source::RegFile::read(Path::new(path))?, false),
+        _ => return Err("Usage: enum-snapins audit [file.reg]".into()),
+    };
+
+    for finding in &findings {
+        println!("{}  {}", finding.clsid, finding);
+    }
+
+    let high = findings.iter().filter(|f| f.risk == audit::Risk::High).count();
+    if high > 0 {
+        return Err(format!("{} high risk module reference(s)", high).into());
+    }
+
+    Ok(())
+}
+
+// Only the live registry's modules can be looked for on disk
+#[cfg(windows)]
+fn audit_live() -> Result<Vec<audit::ModuleFinding>, Box<dyn Error>> {
+    Ok(audit::audit_modules(&source::LiveRegistry, true))
+}
+
+#[cfg(not(windows))]
+fn audit_live() -> Result<Vec<audit::ModuleFinding>, Box<dyn Error>> {
+    Err("Auditing the registry is only available on Windows; pass a .reg file to audit instead".into())
+}
+
+// enum-snapins signatures [file.reg]
+fn check_signatures(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let references = match args {
+        [] => live_module_references()?,
+        [path] => audit::module_references(&source::RegFile::read(Path::new(path))?),
+        _ => return Err("Usage: enum-snapins signatures [file.reg]".into()),
+    };
+
+    let mut mismatches = 0;
+    for (path, clsids) in hashes::group_modules(&references) {
+        println!("{}", path);
+        match authenticode::read(Path::new(&path)) {
+            Ok(Some(signature)) => {
+                println!("    signer: {}", signature.signer.as_deref().unwrap_or("(not found)"));
+                println!("    issuer: {}", signature.issuer.as_deref().unwrap_or("(not found)"));
+                println!("    timestamp: {}", signature.timestamp.as_deref().unwrap_or("(none)"));
+                let digest = match signature.digest_matches {
+                    Some(true) => "matches",
+                    Some(false) => "DIGEST MISMATCH, the file changed after it was signed",
+                    None => "not checked",
+                };
+                println!("    digest: {}, {}", signature.digest_algorithm, digest);
+                if signature.digest_matches == Some(false) {
+                    mismatches += 1;
+                }
+            }
+            Ok(None) => println!("    UNSIGNED: no embedded signature, though it may be signed in a catalog"),
+            Err(e) => println!("    error: {}", e),
+        }
+        println!("    used by: {}", clsids.join(", "));
+    }
+
+    if mismatches > 0 {
+        return Err(format!("{} module(s) don't match their signature", mismatches).into());
+    }
+
+    Ok(())
+}
+
+// enum-snapins hashes [--modules] [inventory.json|file.reg]
+fn list_hashes(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let by_module = args.iter().any(|a| a == "--modules");
+    let paths: Vec<&String> = args.iter().filter(|a| *a != "--modules").collect();
+
+    let inventory = match paths[..] {
+        [] => hashed_inventory(None)?,
+        [path] => hashed_inventory(Some(path))?,
+        _ => return Err("Usage: enum-snapins hashes [--modules] [inventory.json|file.reg]".into()),
+    };
+
+    if by_module {
+        for module in &inventory.modules {
+            println!("{}", module.path);
+            if let Some(error) = &module.error {
+                println!("    error: {}", error);
+            }
+            for (name, hash) in [
+                ("sha256", &module.sha256),
+                ("sha1", &module.sha1),
+                ("authenticode sha256", &module.authenticode_sha256),
+                ("authenticode sha1", &module.authenticode_sha1),
+            ] {
+                if let Some(hash) = hash {
+                    println!("    {}: {}", name, hash);
+                }
+            }
+            for clsid in &module.snapins {
+                let name = inventory.snapins.iter().find(|s| snapin::normalize_clsid(&s.clsid).as_ref() == Some(clsid)).map_or("", |s| s.get_name());
+                println!("    used by: {}  {}", clsid, name);
+            }
+        }
+    } else {
+        for snapin in &inventory.snapins {
+            println!("{}  {}", snapin.clsid, snapin.get_name());
+            for module in inventory.modules_of(&snapin.clsid) {
+                let hash = module.sha256.as_deref().or(module.error.as_deref()).unwrap_or("");
+                println!("    {}: {}", module.path, hash);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// enum-snapins policy <policy.toml|policy.json> [inventory.json|file.reg]
+fn check_policy(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (policy_path, inventory_path) = match args {
+        [policy] => (policy, None),
+        [policy, inventory] => (policy, Some(inventory)),
+        _ => return Err("Usage: enum-snapins policy <policy.toml|policy.json> [inventory.json|file.reg]".into()),
+    };
+
+    let policy = policy::Policy::read(Path::new(policy_path))?;
+    let inventory = hashed_inventory(inventory_path)?;
+    if policy.checks_modules() && inventory.modules.is_empty() && !inventory.snapins.is_empty() {
+        eprintln!("The inventory lists no modules, so they can't be checked; export it again");
     }
 
-    Ok(found_snapins)
+    let violations = policy.evaluate(&inventory);
+    for violation in &violations {
+        println!("{}", violation);
+    }
+
+    if !violations.is_empty() {
+        return Err(format!("{} policy violation(s)", violations.len()).into());
+    }
+
+    Ok(())
+}
+
+// enum-snapins report <report.md|report.html> [inventory.json|file.reg]
+fn write_report(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (output_path, snapins) = match args {
+        [output] => (output, filtered(report_snapins()?)),
+        [output, path] => (output, filtered(inventory::load(Path::new(path))?.snapins)),
+        _ => return Err("Usage: enum-snapins report <report.md|report.html> [inventory.json|file.reg]".into()),
+    };
+
+    let output = output_path.to_lowercase();
+    let report = if output.ends_with(".md") {
+        report::markdown(&snapins)
+    } else if output.ends_with(".html") || output.ends_with(".htm") {
+        report::html(&snapins)
+    } else {
+        return Err(format!("Can't tell the report format of {}; name it .md or .html", output_path).into());
+    };
+
+    fs::write(output_path, report)?;
+    Ok(())
+}
+
+// enum-snapins summary [--json] [inventory.json|file.reg]
+fn summarize(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let json = args.iter().any(|a| a == "--json");
+    let paths: Vec<&String> = args.iter().filter(|a| *a != "--json").collect();
+
+    let snapins = match paths[..] {
+        [] => get_snapins()?,
+        [path] => inventory::load(Path::new(path))?.snapins,
+        _ => return Err("Usage: enum-snapins summary [--json] [inventory.json|file.reg]".into()),
+    };
+
+    let summary = summary::Summary::new(&filtered(snapins));
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        print!("{}", summary);
+    }
+
+    Ok(())
+}
+
+// enum-snapins query <query> [inventory.json|file.reg]
+fn run_query(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (query, snapins) = match args {
+        [query] => (query, get_snapins()?),
+        [query, path] => (query, inventory::load(Path::new(path))?.snapins),
+        _ => return Err("Usage: enum-snapins query <query> [inventory.json|file.reg]".into()),
+    };
+
+    let query = parse_query(query)?;
+    for snapin in filtered(snapins).iter().filter(|s| query.matches(s)) {
+        println!("{}  {}", snapin.clsid, snapin.get_name());
+    }
+
+    Ok(())
+}
+
+// Parses `query`, with a caret under where it goes wrong if it doesn't
+fn parse_query(query: &str) -> Result<query::Query, Box<dyn Error>> {
+    query::Query::parse(query).map_err(|e| e.show(query).into())
+}
+
+// The snap-ins matching --where, or all of them without it
+fn filtered(mut snapins: Vec<MMCSnapIn>) -> Vec<MMCSnapIn> {
+    if let Some(filter) = &OPTIONS.get_or_init(Options::default).filter {
+        snapins.retain(|s| filter.matches(s));
+    }
+    snapins
+}
+
+// The live registry's snap-ins, with their About images for the icons
+#[cfg(windows)]
+fn report_snapins() -> Result<Vec<MMCSnapIn>, Box<dyn Error>> {
+    get_live_snapins(true)
+}
+
+#[cfg(not(windows))]
+fn report_snapins() -> Result<Vec<MMCSnapIn>, Box<dyn Error>> {
+    get_snapins()
+}
+
+// The live registry's snap-ins, a saved inventory, or a .reg file's
+// registrations with their modules looked for on this machine
+fn hashed_inventory(path: Option<&String>) -> Result<inventory::Inventory, Box<dyn Error>> {
+    match path {
+        None => Ok(inventory::Inventory { snapins: get_snapins()?, modules: hashes::hash_modules(&live_module_references()?) }),
+        Some(path) if path.to_lowercase().ends_with(".reg") => {
+            let file = source::RegFile::read(Path::new(path))?;
+            let snapins = snapin::get_snapins(&file, &snapin::NoResolver, 1, &[])?;
+            Ok(inventory::Inventory { snapins, modules: hashes::hash_modules(&audit::module_references(&file)) })
+        }
+        Some(path) => inventory::load(Path::new(path)),
+    }
+}
+
+#[cfg(windows)]
+fn live_module_references() -> Result<Vec<audit::ModuleReference>, Box<dyn Error>> {
+    Ok(audit::module_references(&source::LiveRegistry))
+}
+
+#[cfg(not(windows))]
+fn live_module_references() -> Result<Vec<audit::ModuleReference>, Box<dyn Error>> {
+    Err("Reading the registry is only available on Windows; pass a .reg file instead".into())
+}
+
+fn find_consoles(path: &Path, found: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            find_consoles(&entry?.path(), found)?;
+        }
+    } else if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("msc")) {
+        found.push(path.to_path_buf());
+    }
+
+    Ok(())
+}
+
+// enum-snapins export <inventory.json>
+fn export_inventory(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let [output_path] = args else {
+        return Err("Usage: enum-snapins export <inventory.json>".into());
+    };
+
+    let snapins = filtered(get_snapins()?);
+    let mut modules = hashes::hash_modules(&live_module_references()?);
+    if OPTIONS.get_or_init(Options::default).filter.is_some() {
+        modules.retain(|m| snapins.iter().any(|s| snapin::normalize_clsid(&s.clsid).is_some_and(|c| m.snapins.contains(&c))));
+    }
+    let inventory = inventory::Inventory { snapins, modules };
+    inventory::save(Path::new(output_path), &inventory)?;
+
+    // Report strings with no translation in a language asked for
+    let languages = &OPTIONS.get_or_init(Options::default).languages;
+    for snapin in &inventory.snapins {
+        let missing = snapin.missing_translations(languages);
+        if !missing.is_empty() {
+            eprintln!("{}  {}: missing {}", snapin.clsid, snapin.get_name(), missing.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+// enum-snapins diff <old.json> [new.json] [--json]
+fn diff_inventories(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let json = args.iter().any(|a| a == "--json");
+    let paths: Vec<&String> = args.iter().filter(|a| *a != "--json").collect();
+
+    let (old, new) = match paths[..] {
+        [old] => (inventory::load(Path::new(old))?.snapins, get_snapins()?),
+        [old, new] => (inventory::load(Path::new(old))?.snapins, inventory::load(Path::new(new))?.snapins),
+        _ => return Err("Usage: enum-snapins diff <old.json> [new.json] [--json]".into()),
+    };
+
+    let diff = diff::InventoryDiff::new(&old, &new);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else if diff.is_empty() {
+        println!("No changes");
+    } else {
+        print!("{}", diff);
+    }
+
+    Ok(())
+}
+
+// enum-snapins tui [inventory.json|file.reg]
+fn run_tui(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let snapins = match args {
+        [] => get_snapins()?,
+        [path] => inventory::load(Path::new(path))?.snapins,
+        _ => return Err("Usage: enum-snapins tui [inventory.json|file.reg]".into()),
+    };
+
+    tui::run(snapins)
+}
+
+// enum-snapins watch [--interval <seconds>] [file.reg]
+fn watch_snapins(args: &[String]) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = "Usage: enum-snapins watch [--interval <seconds>] [file.reg]";
+
+    let (interval, args) = match args {
+        [flag, seconds, rest @ ..] if flag == "--interval" => {
+            let seconds: f64 = seconds.parse().map_err(|_| USAGE)?;
+            (Duration::try_from_secs_f64(seconds).map_err(|_| USAGE)?, rest)
+        }
+        rest => (WATCH_INTERVAL, rest),
+    };
+
+    match args {
+        [] => watch_live(interval),
+        [path] => watch_file(Path::new(path), interval),
+        _ => Err(USAGE.into()),
+    }
+}
+
+// Polls a .reg file, for trying registrations out before importing them
+fn watch_file(path: &Path, interval: Duration) -> Result<(), Box<dyn Error>> {
+    let file = source::RegFile::read(path)?;
+    let snapins = snapin::get_snapins(&file, &snapin::NoResolver, 1, &[])?;
+    let mut watcher = watch::Watcher::new(&file, &snapin::NoResolver, &[], &snapins);
+    println!("Watching {} snap-ins in {}", snapins.len(), path.display());
+
+    let mut trigger = watch::Trigger::poll(interval);
+    loop {
+        trigger.wait();
+        match source::RegFile::read(path) {
+            Ok(file) => print!("{}", watcher.refresh(&file).diff),
+            Err(e) => log::warn!("Couldn't read {}: {}", path.display(), e),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn watch_live(interval: Duration) -> Result<(), Box<dyn Error>> {
+    let snapins = get_snapins()?;
+    let (resolver, _) = live_resolver()?;
+    let languages = &OPTIONS.get_or_init(Options::default).languages;
+    let mut watcher = watch::Watcher::new(&source::LiveRegistry, &resolver, languages, &snapins);
+    println!("Watching {} snap-ins", snapins.len());
+
+    let mut trigger = watch::Trigger::live(interval);
+    loop {
+        trigger.wait();
+        print!("{}", watcher.refresh(&source::LiveRegistry).diff);
+    }
+}
+
+#[cfg(not(windows))]
+fn watch_live(_interval: Duration) -> Result<(), Box<dyn Error>> {
+    Err("Watching the registry is only available on Windows; pass a .reg file to watch instead".into())
+}
+
+// enum-snapins about-worker <clsid>
+//
+// Probes one About object for IsolatedResolver and prints it, or why it
+// can't be read for good, as JSON.
+#[cfg(windows)]
+fn probe_about(args: &[String]) -> Result<(), Box<dyn Error>> {
+    use snapin::Resolver;
+
+    let [clsid] = args else {
+        return Err("Usage: enum-snapins about-worker <clsid>".into());
+    };
+
+    println!("{}", snapin::worker_answer(snapin::NativeResolver.about(clsid))?);
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn probe_about(_args: &[String]) -> Result<(), Box<dyn Error>> {
+    Err("Probing About objects is only available on Windows".into())
+}
+
+#[cfg(windows)]
+fn get_snapins() -> Result<Vec<MMCSnapIn>, Box<dyn Error>> {
+    get_live_snapins(false)
+}
+
+#[cfg(windows)]
+fn get_live_snapins(images: bool) -> Result<Vec<MMCSnapIn>, Box<dyn Error>> {
+    let (resolver, cache) = live_resolver()?;
+    resolve_live(resolver, cache, images)
+}
+
+// A resolver and the name of its cache
+#[cfg(windows)]
+type NamedResolver = (Box<dyn snapin::Resolver>, &'static str);
+
+// The resolver the options ask for
+#[cfg(windows)]
+fn live_resolver() -> Result<NamedResolver, Box<dyn Error>> {
+    let options = OPTIONS.get_or_init(Options::default);
+
+    if options.safe {
+        return Ok((Box::new(snapin::StaticResolver::new(&source::LiveRegistry)), "safe"));
+    }
+
+    match options.isolate {
+        Some(timeout) => Ok((Box::new(snapin::IsolatedResolver::new(snapin::NativeResolver, timeout)?), "isolated")),
+        None => Ok((Box::new(snapin::NativeResolver), "native")),
+    }
+}
+
+// Enumerates the live registry with `resolver`, through the cache named
+// `cache` unless --no-cache was given
+#[cfg(windows)]
+fn resolve_live<R: snapin::Resolver>(resolver: R, cache: &str, images: bool) -> Result<Vec<MMCSnapIn>, Box<dyn Error>> {
+    let options = OPTIONS.get_or_init(Options::default);
+    let jobs = options.jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+
+    let cache_path = snapin::cache::default_path(cache);
+    let Some(cache_path) = cache_path.filter(|_| !options.no_cache) else {
+        return snapin::get_snapins(&source::LiveRegistry, &resolver, jobs, &options.languages);
+    };
+
+    let mut resolver = snapin::CachingResolver::load(resolver, &source::LiveRegistry, &cache_path);
+    if images {
+        resolver = resolver.strings_only();
+    }
+
+    let snapins = snapin::get_snapins(&source::LiveRegistry, &resolver, jobs, &options.languages)?;
+    log::info!("Resolution cache: {}", resolver.stats());
+    if let Err(e) = resolver.save() {
+        log::warn!("Couldn't save the resolution cache {}: {}", cache_path.display(), e);
+    }
+
+    Ok(snapins)
+}
+
+#[cfg(not(windows))]
+fn get_snapins() -> Result<Vec<MMCSnapIn>, Box<dyn Error>> {
+    Err("Enumerating the registry is only available on Windows".into())
 }
@@ -0,0 +1,428 @@
+use std::{collections::BTreeMap, error::Error, fmt::Write, fs, path::Path};
+
+use uuid::Uuid;
+
+use crate::snapin::{normalize_clsid, MMCSnapIn};
+
+// The built-in Folder snap-in MMC uses for "Console Root"
+const CONSOLE_ROOT_CLSID: &str = "{C96401CC-0E17-11D3-885B-00C04F72C717}";
+// The string table MMC keeps node and favorite names in
+const STRING_TABLE_GUID: &str = "{71E5B33E-1064-11D2-808F-0000F875A9CE}";
+
+/// One line of a console config file: a snap-in and the extensions to enable
+/// for it. `None` leaves every registered extension enabled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsoleSelection {
+    pub clsid: String,
+    pub extensions: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsoleSnapIn {
+    pub clsid: String,
+    pub name: String,
+    pub extensions: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Console {
+    pub id: Uuid,
+    pub snapins: Vec<ConsoleSnapIn>,
+}
+
+/// Parses a console config. Each non-empty line names a snap-in CLSID,
+/// optionally followed by `:` and a comma separated list of extension CLSIDs
+/// to enable. An empty list after `:` disables all extensions.
+///
+/// ```text
+/// # Computer Management with only the Event Viewer extension
+/// {58221C67-EA27-11CF-ADCF-00AA00A80033}: {975797FC-4E2A-11D0-B702-00C04FD8DBF7}
+/// ```
+pub fn parse_config(s: &str) -> Result<Vec<ConsoleSelection>, String> {
+    let mut selections = Vec::new();
+
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (clsid, extensions) = match line.split_once(':') {
+            Some((clsid, exts)) => {
+                let exts = exts
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|e| !e.is_empty())
+                    .map(|e| normalize_clsid(e).ok_or(format!("line {}: invalid extension CLSID '{}'", i + 1, e)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                (clsid.trim(), Some(exts))
+            }
+            None => (line, None),
+        };
+
+        let clsid = normalize_clsid(clsid).ok_or(format!("line {}: invalid CLSID '{}'", i + 1, clsid))?;
+        selections.push(ConsoleSelection { clsid, extensions });
+    }
+
+    Ok(selections)
+}
+
+impl ConsoleSnapIn {
+    pub fn new(snapin: &MMCSnapIn, extensions: Option<Vec<String>>) -> Self {
+        let name = match snapin.get_name() {
+            "" => snapin.clsid.clone(),
+            name => name.to_string(),
+        };
+
+        ConsoleSnapIn {
+            clsid: normalize_clsid(&snapin.clsid).unwrap_or(snapin.clsid.clone()),
+            name,
+            extensions,
+        }
+    }
+}
+
+impl Console {
+    pub fn new(snapins: Vec<ConsoleSnapIn>) -> Self {
+        Console { id: Uuid::new_v4(), snapins }
+    }
+
+    /// Resolves each selection against the enumerated snap-ins, failing on
+    /// any CLSID that isn't registered on this machine.
+    pub fn from_selections(selections: &[ConsoleSelection], snapins: &[MMCSnapIn]) -> Result<Self, String> {
+        let mut console_snapins = Vec::new();
+
+        for selection in selections {
+            let snapin = snapins
+                .iter()
+                .find(|s| normalize_clsid(&s.clsid).as_deref() == Some(selection.clsid.as_str()))
+                .ok_or(format!("Snap-in {} is not registered", selection.clsid))?;
+
+            console_snapins.push(ConsoleSnapIn::new(snapin, selection.extensions.clone()));
+        }
+
+        Ok(Console::new(console_snapins))
+    }
+
+    pub fn to_xml(&self) -> String {
+        // String table ids: 1 is the favorites root, 2 the console root and
+        // every snap-in node gets the next one along.
+        let mut strings: Vec<(u32, &str)> = vec![(1, "Favorites"), (2, "Console Root")];
+        for (i, snapin) in self.snapins.iter().enumerate() {
+            strings.push((i as u32 + 3, &snapin.name));
+        }
+        let next_string = strings.len() as u32 + 1;
+
+        let mut xml = String::new();
+        let _ = writeln!(xml, r#"<?xml version="1.0"?>"#);
+        let _ = writeln!(xml, r#"<MMC_ConsoleFile ConsoleVersion="3.0" ProgramMode="Author">"#);
+        let _ = writeln!(xml, "  <ConsoleFileID>{{{}}}</ConsoleFileID>", self.id.as_hyphenated().to_string().to_uppercase());
+        let _ = writeln!(xml, r#"  <FrameState ShowStatusBar="true" LinkToWeb="false">"#);
+        let _ = writeln!(xml, r#"    <WindowPlacement ShowCommand="SW_SHOWNORMAL">"#);
+        let _ = writeln!(xml, r#"      <Point Name="MinPosition" X="-1" Y="-1"/>"#);
+        let _ = writeln!(xml, r#"      <Point Name="MaxPosition" X="-1" Y="-1"/>"#);
+        let _ = writeln!(xml, r#"      <Rectangle Name="NormalPosition" Top="0" Bottom="480" Left="0" Right="640"/>"#);
+        let _ = writeln!(xml, r#"    </WindowPlacement>"#);
+        let _ = writeln!(xml, r#"  </FrameState>"#);
+        let _ = writeln!(xml, r#"  <Views>"#);
+        let _ = writeln!(xml, r#"    <View ID="1" ScopeNode="1" ScopePaneWidth="200" ListViewMode="LVS_REPORT" ViewMode="MMCSS_CLASSIC">"#);
+        let _ = writeln!(xml, r#"      <BookMark Name="RootNode" NodeID="1"/>"#);
+        let _ = writeln!(xml, r#"      <BookMark Name="SelectedNode" NodeID="1"/>"#);
+        let _ = writeln!(xml, r#"    </View>"#);
+        let _ = writeln!(xml, r#"  </Views>"#);
+        let _ = writeln!(xml, r#"  <VisualAttributes>"#);
+        let _ = writeln!(xml, r#"    <String Name="ApplicationTitle" ID="2"/>"#);
+        let _ = writeln!(xml, r#"  </VisualAttributes>"#);
+        let _ = writeln!(xml, r#"  <Favorites>"#);
+        let _ = writeln!(xml, r#"    <Favorite TYPE="Group">"#);
+        let _ = writeln!(xml, r#"      <String Name="Name" ID="1"/>"#);
+        let _ = writeln!(xml, r#"      <Favorites/>"#);
+        let _ = writeln!(xml, r#"    </Favorite>"#);
+        let _ = writeln!(xml, r#"  </Favorites>"#);
+
+        // Console tree: the root folder with one child node per snap-in
+        let _ = writeln!(xml, r#"  <Tree>"#);
+        let _ = writeln!(xml, r#"    <Node ID="1" ImageIdx="0" CLSID="{}" Preload="false">"#, CONSOLE_ROOT_CLSID);
+        let _ = writeln!(xml, r#"      <Nodes>"#);
+        for (i, snapin) in self.snapins.iter().enumerate() {
+            let _ = writeln!(xml, r#"        <Node ID="{}" ImageIdx="0" CLSID="{}" Preload="false">"#, i + 2, snapin.clsid);
+            let _ = writeln!(xml, r#"          <Nodes/>"#);
+            let _ = writeln!(xml, r#"          <String Name="Name" ID="{}"/>"#, i + 3);
+            let _ = writeln!(xml, r#"        </Node>"#);
+        }
+        let _ = writeln!(xml, r#"      </Nodes>"#);
+        let _ = writeln!(xml, r#"      <String Name="Name" ID="2"/>"#);
+        let _ = writeln!(xml, r#"    </Node>"#);
+        let _ = writeln!(xml, r#"  </Tree>"#);
+
+        let _ = writeln!(xml, r#"  <SnapinCache>"#);
+        let _ = writeln!(xml, r#"    <Snapin CLSID="{}" AllExtensionsEnabled="true"/>"#, CONSOLE_ROOT_CLSID);
+        for snapin in &self.snapins {
+            match &snapin.extensions {
+                None => {
+                    let _ = writeln!(xml, r#"    <Snapin CLSID="{}" AllExtensionsEnabled="true"/>"#, snapin.clsid);
+                }
+                Some(extensions) => {
+                    let _ = writeln!(xml, r#"    <Snapin CLSID="{}" AllExtensionsEnabled="false">"#, snapin.clsid);
+                    let _ = writeln!(xml, r#"      <Extensions>"#);
+                    for extension in extensions {
+                        let _ = writeln!(xml, r#"        <Extension CLSID="{}"/>"#, extension);
+                    }
+                    let _ = writeln!(xml, r#"      </Extensions>"#);
+                    let _ = writeln!(xml, r#"    </Snapin>"#);
+                }
+            }
+        }
+        let _ = writeln!(xml, r#"  </SnapinCache>"#);
+
+        let _ = writeln!(xml, r#"  <StringTables>"#);
+        let _ = writeln!(xml, r#"    <IdentifierPool AbsoluteMin="1" AbsoluteMax="65535" NextAvailable="{}"/>"#, next_string);
+        let _ = writeln!(xml, r#"    <StringTable>"#);
+        let _ = writeln!(xml, r#"      <GUID>{}</GUID>"#, STRING_TABLE_GUID);
+        let _ = writeln!(xml, r#"      <Strings>"#);
+        for (id, string) in strings {
+            let _ = writeln!(xml, r#"        <String ID="{}" Refs="1">{}</String>"#, id, escape_xml(string));
+        }
+        let _ = writeln!(xml, r#"      </Strings>"#);
+        let _ = writeln!(xml, r#"    </StringTable>"#);
+        let _ = writeln!(xml, r#"  </StringTables>"#);
+        let _ = writeln!(xml, r#"  <BinaryStorage/>"#);
+        let _ = writeln!(xml, r#"</MMC_ConsoleFile>"#);
+
+        xml
+    }
+
+    pub fn write_to(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_xml())?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsoleNode {
+    pub id: u32,
+    pub clsid: String,
+    pub name: Option<String>,
+}
+
+/// The snap-in related parts of an existing MMC 3.0 console file.
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleFile {
+    pub snapins: Vec<ConsoleSelection>,
+    pub nodes: Vec<ConsoleNode>,
+    pub strings: BTreeMap<u32, String>,
+}
+
+impl ConsoleFile {
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let bytes = fs::read(path)?;
+
+        // MMC writes UTF-8, but consoles saved by other tools are sometimes
+        // UTF-16 with a byte order mark.
+        let xml = if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+            let wide: Vec<u16> = rest.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&wide)
+        } else if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+            String::from_utf8(rest.to_vec())?
+        } else {
+            String::from_utf8(bytes).map_err(|_| "Not an XML console file (MMC 2.0 or older?)")?
+        };
+
+        ConsoleFile::parse(&xml)
+    }
+
+    pub fn parse(xml: &str) -> Result<Self, Box<dyn Error>> {
+        let doc = roxmltree::Document::parse(xml)?;
+        let root = doc.root_element();
+        if !root.has_tag_name("MMC_ConsoleFile") {
+            return Err("Not an MMC console file".into());
+        }
+
+        let mut console = ConsoleFile::default();
+
+        for table in root.descendants().filter(|n| n.has_tag_name("StringTable")) {
+            for string in table.descendants().filter(|n| n.has_tag_name("String")) {
+                if let Some(id) = string.attribute("ID").and_then(|id| id.parse().ok()) {
+                    console.strings.insert(id, string.text().unwrap_or("").to_string());
+                }
+            }
+        }
+
+        if let Some(cache) = root.children().find(|n| n.has_tag_name("SnapinCache")) {
+            for snapin in cache.children().filter(|n| n.has_tag_name("Snapin")) {
+                let Some(clsid) = snapin.attribute("CLSID") else { continue };
+                let extensions = match snapin.attribute("AllExtensionsEnabled") {
+                    Some("false") => Some(
+                        snapin
+                            .descendants()
+                            .filter(|n| n.has_tag_name("Extension"))
+                            .filter_map(|n| n.attribute("CLSID"))
+                            .map(|c| normalize_clsid(c).unwrap_or(c.to_string()))
+                            .collect()
+                    ),
+                    _ => None,
+                };
+
+                console.snapins.push(ConsoleSelection {
+                    clsid: normalize_clsid(clsid).unwrap_or(clsid.to_string()),
+                    extensions,
+                });
+            }
+        }
+
+        if let Some(tree) = root.children().find(|n| n.has_tag_name("Tree")) {
+            for node in tree.descendants().filter(|n| n.has_tag_name("Node")) {
+                let Some(clsid) = node.attribute("CLSID") else { continue };
+                let name = node
+                    .children()
+                    .find(|n| n.has_tag_name("String") && n.attribute("Name") == Some("Name"))
+                    .and_then(|n| n.attribute("ID"))
+                    .and_then(|id| id.parse().ok())
+                    .and_then(|id| console.strings.get(&id).cloned());
+
+                console.nodes.push(ConsoleNode {
+                    id: node.attribute("ID").and_then(|id| id.parse().ok()).unwrap_or(0),
+                    clsid: normalize_clsid(clsid).unwrap_or(clsid.to_string()),
+                    name,
+                });
+            }
+        }
+
+        Ok(console)
+    }
+
+    /// Snap-ins the console loads, whether from the snap-in cache or as a
+    /// node in the console tree, that aren't in the enumerated inventory.
+    pub fn unregistered(&self, snapins: &[MMCSnapIn]) -> Vec<&str> {
+        let mut missing: Vec<&str> = Vec::new();
+        let clsids = self.snapins.iter().map(|s| s.clsid.as_str())
+            .chain(self.nodes.iter().map(|n| n.clsid.as_str()));
+
+        for clsid in clsids {
+            let registered = snapins
+                .iter()
+                .any(|s| normalize_clsid(&s.clsid).as_deref() == Some(clsid));
+            if !registered && !missing.contains(&clsid) {
+                missing.push(clsid);
+            }
+        }
+
+        missing
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_snapin(clsid: &str, name: &str) -> MMCSnapIn {
+        MMCSnapIn {
+            clsid: clsid.into(),
+            namestring: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = r"
+            # comment
+            {58221c67-ea27-11cf-adcf-00aa00a80033}
+            {975797FC-4E2A-11D0-B702-00C04FD8DBF7}: {394C052E-B830-11D0-9A86-00C04FD8DBF7}
+            {5D6179C8-17EC-11D1-9AA9-00C04FD8FE93}:
+        ";
+        let selections = parse_config(config).unwrap();
+
+        assert_eq!(selections, vec![
+            ConsoleSelection {
+                clsid: "{58221C67-EA27-11CF-ADCF-00AA00A80033}".into(),
+                extensions: None,
+            },
+            ConsoleSelection {
+                clsid: "{975797FC-4E2A-11D0-B702-00C04FD8DBF7}".into(),
+                extensions: Some(vec!["{394C052E-B830-11D0-9A86-00C04FD8DBF7}".into()]),
+            },
+            ConsoleSelection {
+                clsid: "{5D6179C8-17EC-11D1-9AA9-00C04FD8FE93}".into(),
+                extensions: Some(vec![]),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_config_invalid_clsid() {
+        let result = parse_config("{not-a-guid}");
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_from_selections_unregistered() {
+        let selections = parse_config("{58221C67-EA27-11CF-ADCF-00AA00A80033}").unwrap();
+        let result = Console::from_selections(&selections, &[]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_to_xml() {
+        let snapins = vec![
+            test_snapin("{58221c67-ea27-11cf-adcf-00aa00a80033}", "Computer Management"),
+            test_snapin("{975797FC-4E2A-11D0-B702-00C04FD8DBF7}", "Event Viewer & Logs"),
+        ];
+        let selections = parse_config(
+            "{58221C67-EA27-11CF-ADCF-00AA00A80033}\n{975797FC-4E2A-11D0-B702-00C04FD8DBF7}:"
+        ).unwrap();
+        let xml = Console::from_selections(&selections, &snapins).unwrap().to_xml();
+
+        assert!(xml.contains(r#"<Node ID="2" ImageIdx="0" CLSID="{58221C67-EA27-11CF-ADCF-00AA00A80033}" Preload="false">"#));
+        assert!(xml.contains(r#"<String ID="3" Refs="1">Computer Management</String>"#));
+        assert!(xml.contains(r#"<String ID="4" Refs="1">Event Viewer &amp; Logs</String>"#));
+        assert!(xml.contains(r#"<Snapin CLSID="{975797FC-4E2A-11D0-B702-00C04FD8DBF7}" AllExtensionsEnabled="false">"#));
+        assert!(xml.contains(r#"<IdentifierPool AbsoluteMin="1" AbsoluteMax="65535" NextAvailable="5"/>"#));
+    }
+
+    #[test]
+    fn test_parse_generated_console() {
+        let snapins = vec![
+            test_snapin("{58221C67-EA27-11CF-ADCF-00AA00A80033}", "Computer Management"),
+            test_snapin("{975797FC-4E2A-11D0-B702-00C04FD8DBF7}", "Event Viewer"),
+        ];
+        let selections = parse_config(
+            "{58221C67-EA27-11CF-ADCF-00AA00A80033}\n{975797FC-4E2A-11D0-B702-00C04FD8DBF7}: {394C052E-B830-11D0-9A86-00C04FD8DBF7}"
+        ).unwrap();
+        let xml = Console::from_selections(&selections, &snapins).unwrap().to_xml();
+        let console = ConsoleFile::parse(&xml).unwrap();
+
+        assert_eq!(&console.snapins[1..], &selections[..]);
+        assert_eq!(console.nodes[1], ConsoleNode {
+            id: 2,
+            clsid: "{58221C67-EA27-11CF-ADCF-00AA00A80033}".into(),
+            name: Some("Computer Management".into()),
+        });
+        assert_eq!(console.strings.get(&2).map(String::as_str), Some("Console Root"));
+        assert_eq!(console.unregistered(&snapins), vec![CONSOLE_ROOT_CLSID]);
+    }
+
+    #[test]
+    fn test_parse_not_a_console() {
+        let result = ConsoleFile::parse("<?xml version=\"1.0\"?><Other/>");
+
+        assert!(result.is_err());
+    }
+}
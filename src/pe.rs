@@ -0,0 +1,359 @@
+use std::{error::Error, fs, path::Path};
+
+use object::pe::{ImageNtHeaders32, ImageNtHeaders64, RT_GROUP_ICON, RT_ICON, RT_STRING, RT_VERSION};
+use object::read::pe::{ImageNtHeaders, PeFile, ResourceDirectoryEntryData};
+use object::{FileKind, LittleEndian as LE};
+
+/// A resource read straight out of a PE file.
+struct Resource {
+    kind: u16,
+    // `None` for resources with a string name
+    id: Option<u16>,
+    language: u16,
+    data: Vec<u8>,
+}
+
+/// The resources of a DLL or EXE, read from the file on disk. Nothing in the
+/// file is loaded or run, so it's safe to use on modules we don't trust.
+pub struct PeResources {
+    resources: Vec<Resource>,
+}
+
+impl PeResources {
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        PeResources::parse(&data)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        match FileKind::parse(data)? {
+            FileKind::Pe32 => PeResources::parse_as::<ImageNtHeaders32>(data),
+            FileKind::Pe64 => PeResources::parse_as::<ImageNtHeaders64>(data),
+            _ => Err("not a PE file".into()),
+        }
+    }
+
+    fn parse_as<Pe: ImageNtHeaders>(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let file = PeFile::<Pe>::parse(data)?;
+        let sections = file.section_table();
+        let mut resources = Vec::new();
+
+        let Some(directory) = file.data_directories().resource_directory(data, &sections)? else {
+            return Ok(PeResources { resources });
+        };
+
+        // Resources are a three level tree: type, then name, then language
+        for type_entry in directory.root()?.entries {
+            let Some(kind) = type_entry.name_or_id().id() else { continue };
+            let ResourceDirectoryEntryData::Table(names) = type_entry.data(directory)? else { continue };
+
+            for name_entry in names.entries {
+                let id = name_entry.name_or_id().id();
+                let ResourceDirectoryEntryData::Table(languages) = name_entry.data(directory)? else { continue };
+
+                for language_entry in languages.entries {
+                    let Some(language) = language_entry.name_or_id().id() else { continue };
+                    let ResourceDirectoryEntryData::Data(entry) = language_entry.data(directory)? else { continue };
+
+                    let size = entry.size.get(LE) as usize;
+                    let data = sections
+                        .pe_data_at(data, entry.offset_to_data.get(LE))
+                        .and_then(|d| d.get(..size))
+                        .ok_or("resource data is outside the file")?;
+                    resources.push(Resource { kind, id, language, data: data.to_vec() });
+                }
+            }
+        }
+
+        Ok(PeResources { resources })
+    }
+
+    // The first language of a resource
+    fn resource(&self, kind: u16, id: u16) -> Option<&[u8]> {
+        self.resources
+            .iter()
+            .find(|r| r.kind == kind && r.id == Some(id))
+            .map(|r| r.data.as_slice())
+    }
+
+    /// A string from the string table, as `LoadString` would return it.
+    pub fn string(&self, id: u32) -> Option<String> {
+        // Strings are stored in blocks of 16, block n + 1 holding ids 16n to 16n + 15
+        let block = self.resource(RT_STRING, u16::try_from(id / 16 + 1).ok()?)?;
+        string_from_block(block, (id % 16) as usize)
+    }
+
+    /// A string from the string table for `language` (a Windows LANGID),
+    /// if the module has one in that language.
+    pub fn string_in(&self, id: u32, language: u16) -> Option<String> {
+        let block_id = u16::try_from(id / 16 + 1).ok()?;
+        let block = self.resources
+            .iter()
+            .find(|r| r.kind == RT_STRING && r.id == Some(block_id) && r.language == language)?;
+        string_from_block(&block.data, (id % 16) as usize)
+    }
+
+    pub fn version_info(&self) -> Option<VersionInfo> {
+        let data = self.resources.iter().find(|r| r.kind == RT_VERSION)?;
+        VersionInfo::parse(&data.data)
+    }
+
+    /// The image of the module's first icon closest to 32x32, in the format
+    /// `CreateIconFromResourceEx` takes.
+    pub fn icon(&self) -> Option<&[u8]> {
+        let group = self.resources.iter().find(|r| r.kind == RT_GROUP_ICON)?;
+        self.resource(RT_ICON, best_icon(&group.data)?)
+    }
+}
+
+// The languages Windows ships in, by tag and LANGID
+const LANGUAGES: &[(&str, u16)] = &[
+    ("ar-SA", 0x0401), ("bg-BG", 0x0402), ("zh-TW", 0x0404), ("cs-CZ", 0x0405),
+    ("da-DK", 0x0406), ("de-DE", 0x0407), ("el-GR", 0x0408), ("en-US", 0x0409),
+    ("fi-FI", 0x040B), ("fr-FR", 0x040C), ("he-IL", 0x040D), ("hu-HU", 0x040E),
+    ("it-IT", 0x0410), ("ja-JP", 0x0411), ("ko-KR", 0x0412), ("nl-NL", 0x0413),
+    ("nb-NO", 0x0414), ("pl-PL", 0x0415), ("pt-BR", 0x0416), ("ro-RO", 0x0418),
+    ("ru-RU", 0x0419), ("hr-HR", 0x041A), ("sk-SK", 0x041B), ("sv-SE", 0x041D),
+    ("th-TH", 0x041E), ("tr-TR", 0x041F), ("uk-UA", 0x0422), ("sl-SI", 0x0424),
+    ("et-EE", 0x0425), ("lv-LV", 0x0426), ("lt-LT", 0x0427), ("zh-CN", 0x0804),
+    ("en-GB", 0x0809), ("es-MX", 0x080A), ("pt-PT", 0x0816), ("sr-Latn-RS", 0x241A),
+    ("es-ES", 0x0C0A), ("fr-CA", 0x0C0C),
+];
+
+/// The Windows LANGID of a language tag like `de-DE`, for the languages
+/// Windows ships in.
+pub fn language_id(tag: &str) -> Option<u16> {
+    LANGUAGES
+        .iter()
+        .find(|(t, _)| t.eq_ignore_ascii_case(tag))
+        .map(|&(_, id)| id)
+}
+
+/// The language tag of a Windows LANGID, the reverse of `language_id`.
+pub fn language_tag(id: u16) -> Option<&'static str> {
+    LANGUAGES.iter().find(|&&(_, i)| i == id).map(|&(tag, _)| tag)
+}
+
+fn words(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect()
+}
+
+// A string table block is 16 length-prefixed UTF-16 strings
+fn string_from_block(block: &[u8], index: usize) -> Option<String> {
+    let words = words(block);
+    let mut pos = 0;
+
+    for i in 0..16 {
+        let len = *words.get(pos)? as usize;
+        pos += 1;
+        if i == index {
+            let string = words.get(pos..pos + len)?;
+            return (len > 0).then(|| String::from_utf16_lossy(string));
+        }
+        pos += len;
+    }
+
+    None
+}
+
+// Picks the icon in an icon group closest to 32x32, with the most colours
+fn best_icon(group: &[u8]) -> Option<u16> {
+    let count = u16::from_le_bytes(group.get(4..6)?.try_into().ok()?) as usize;
+
+    (0..count)
+        .filter_map(|i| group.get(6 + i * 14..6 + (i + 1) * 14))
+        .map(|entry| {
+            // A width of 0 means 256
+            let width = if entry[0] == 0 { 256 } else { entry[0] as i32 };
+            let bit_count = u16::from_le_bytes([entry[6], entry[7]]);
+            let id = u16::from_le_bytes([entry[12], entry[13]]);
+            ((width - 32).abs(), u16::MAX - bit_count, id)
+        })
+        .min()
+        .map(|(_, _, id)| id)
+}
+
+/// The `VS_VERSIONINFO` resource: the fixed file version and the
+/// `StringFileInfo` strings (CompanyName, FileDescription, ...).
+#[derive(Debug, Default, PartialEq)]
+pub struct VersionInfo {
+    pub file_version: Option<String>,
+    pub strings: Vec<(String, String)>,
+}
+
+impl VersionInfo {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let (root, _) = VersionBlock::parse(data)?;
+        if root.key != "VS_VERSION_INFO" {
+            return None;
+        }
+
+        let mut info = VersionInfo::default();
+
+        // VS_FIXEDFILEINFO starts with a signature, the struct version, then
+        // the file version as two DWORDs
+        let fixed = words(root.value);
+        if fixed.len() >= 8 && fixed[0] == 0x04BD && fixed[1] == 0xFEEF {
+            info.file_version = Some(format!("{}.{}.{}.{}", fixed[5], fixed[4], fixed[7], fixed[6]));
+        }
+
+        // StringFileInfo holds a table per language and code page; the
+        // first one is what Explorer shows
+        for child in root.children.iter().filter(|c| c.key == "StringFileInfo") {
+            if let Some(table) = child.children.first() {
+                for string in &table.children {
+                    let value = String::from_utf16_lossy(&words(string.value));
+                    info.strings.push((string.key.clone(), value.trim_end_matches('\0').to_string()));
+                }
+            }
+        }
+
+        Some(info)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.strings
+            .iter()
+            .find(|(n, v)| n.eq_ignore_ascii_case(name) && !v.is_empty())
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// One node of the version resource tree
+struct VersionBlock<'a> {
+    key: String,
+    value: &'a [u8],
+    children: Vec<VersionBlock<'a>>,
+}
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+impl<'a> VersionBlock<'a> {
+    // Parses a block, returning it and how far along the next sibling starts
+    fn parse(data: &'a [u8]) -> Option<(Self, usize)> {
+        let header = words(data.get(..6)?);
+        let (length, value_length, text) = (header[0] as usize, header[1] as usize, header[2] == 1);
+        let block = data.get(..length).filter(|_| length >= 6)?;
+
+        let key_words = words(&block[6..]);
+        let key_len = key_words.iter().position(|&c| c == 0)?;
+        let key = String::from_utf16_lossy(&key_words[..key_len]);
+
+        // Text values are measured in characters, binary ones in bytes
+        let value_start = align4(6 + (key_len + 1) * 2).min(length);
+        let value_end = (value_start + if text { value_length * 2 } else { value_length }).min(length);
+        let value = &block[value_start..value_end];
+
+        let mut children = Vec::new();
+        let mut pos = align4(value_end);
+        while pos < length {
+            let Some((child, next)) = VersionBlock::parse(&block[pos..]) else { break };
+            children.push(child);
+            pos += next;
+        }
+
+        Some((VersionBlock { key, value, children }, align4(length)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16().chain(Some(0)).flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    fn pad(data: &mut Vec<u8>) {
+        while !data.len().is_multiple_of(4) {
+            data.push(0);
+        }
+    }
+
+    fn block(key: &str, value: &[u8], text: bool, children: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0; 6];
+        data.extend(utf16(key));
+        pad(&mut data);
+        data.extend(value);
+        for child in children {
+            pad(&mut data);
+            data.extend(child);
+        }
+
+        let value_length = if text { value.len() / 2 } else { value.len() };
+        let length = data.len() as u16;
+        data[0..2].copy_from_slice(&length.to_le_bytes());
+        data[2..4].copy_from_slice(&(value_length as u16).to_le_bytes());
+        data[4..6].copy_from_slice(&(text as u16).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_string_from_block() {
+        let mut block = Vec::new();
+        for i in 0..16u16 {
+            let s = if i == 10 { "Computer Management".to_string() } else if i == 3 { String::new() } else { format!("s{}", i) };
+            block.extend((s.len() as u16).to_le_bytes());
+            block.extend(s.encode_utf16().flat_map(|c| c.to_le_bytes()));
+        }
+
+        assert_eq!(string_from_block(&block, 10).as_deref(), Some("Computer Management"));
+        assert_eq!(string_from_block(&block, 0).as_deref(), Some("s0"));
+        assert_eq!(string_from_block(&block, 3), None);
+        assert_eq!(string_from_block(&block[..20], 10), None);
+    }
+
+    #[test]
+    fn test_version_info() {
+        let mut fixed = vec![0u8; 52];
+        fixed[0..4].copy_from_slice(&0xFEEF04BDu32.to_le_bytes());
+        fixed[8..12].copy_from_slice(&(10u32 << 16).to_le_bytes());
+        fixed[12..16].copy_from_slice(&((19041u32 << 16) | 1).to_le_bytes());
+
+        let strings = block("040904B0", &[], true, &[
+            block("CompanyName", &utf16("Microsoft Corporation"), true, &[]),
+            block("FileDescription", &utf16("Computer Management Snap-In"), true, &[]),
+            block("Comments", &utf16(""), true, &[]),
+        ]);
+        let data = block("VS_VERSION_INFO", &fixed, false, &[
+            block("StringFileInfo", &[], true, &[strings]),
+            block("VarFileInfo", &[], true, &[]),
+        ]);
+
+        let info = VersionInfo::parse(&data).unwrap();
+        assert_eq!(info.file_version.as_deref(), Some("10.0.19041.1"));
+        assert_eq!(info.get("companyname"), Some("Microsoft Corporation"));
+        assert_eq!(info.get("FileDescription"), Some("Computer Management Snap-In"));
+        assert_eq!(info.get("Comments"), None);
+    }
+
+    #[test]
+    fn test_best_icon() {
+        let mut group = vec![0, 0, 1, 0, 3, 0];
+        for (width, bit_count, id) in [(16u8, 32u16, 1u16), (32, 8, 2), (32, 32, 3)] {
+            group.extend([width, width, 0, 0, 1, 0]);
+            group.extend(bit_count.to_le_bytes());
+            group.extend(1000u32.to_le_bytes());
+            group.extend(id.to_le_bytes());
+        }
+
+        assert_eq!(best_icon(&group), Some(3));
+        assert_eq!(best_icon(&group[..6]), None);
+    }
+
+    #[test]
+    fn test_language_id() {
+        assert_eq!(language_id("de-DE"), Some(0x0407));
+        assert_eq!(language_id("ja-jp"), Some(0x0411));
+        assert_eq!(language_id("tlh"), None);
+        assert_eq!(language_tag(0x0407), Some("de-DE"));
+        assert_eq!(language_tag(0x7f00), None);
+    }
+
+    #[test]
+    fn test_not_a_pe_file() {
+        assert!(PeResources::parse(b"not a module").is_err());
+    }
+}
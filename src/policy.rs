@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::{error::Error, fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::authenticode::{self, Signature};
+use crate::inventory::Inventory;
+use crate::snapin::{module_path, normalize_clsid};
+
+/// An approved baseline of snap-ins, read from TOML or JSON. Every list
+/// allows anything when left out.
+///
+/// ```text
+/// allowed_clsids = ["{58221C67-EA27-11CF-ADCF-00AA00A80033}"]
+/// required_providers = ["Microsoft Corporation"]
+/// allowed_module_directories = ['%SystemRoot%\System32']
+/// require_signature = true
+/// allowed_signers = ["Microsoft Windows"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// The snap-ins that may be registered.
+    #[serde(default)]
+    pub allowed_clsids: Vec<String>,
+    /// Every snap-in's provider must be one of these.
+    #[serde(default)]
+    pub required_providers: Vec<String>,
+    /// Where modules may be, subdirectories included.
+    #[serde(default)]
+    pub allowed_module_directories: Vec<String>,
+    /// Modules must have an embedded signature their digest matches.
+    #[serde(default)]
+    pub require_signature: bool,
+    /// Who may sign modules, by common name or whole subject. Implies
+    /// `require_signature`.
+    #[serde(default)]
+    pub allowed_signers: Vec<String>,
+}
+
+/// A snap-in that doesn't meet the policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub clsid: String,
+    pub name: String,
+    pub message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}  {}: {}", self.clsid, self.name, self.message)
+    }
+}
+
+impl Policy {
+    /// Reads a `.json` policy, or anything else as TOML.
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let policy = match path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json")) {
+            true => Policy::parse_json(&text),
+            false => Policy::parse_toml(&text),
+        };
+        Ok(policy.map_err(|e| format!("{}: {}", path.display(), e))?)
+    }
+
+    pub fn parse_toml(toml: &str) -> Result<Self, String> {
+        toml::from_str(toml).map_err(|e| e.to_string())
+    }
+
+    pub fn parse_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+
+    /// Whether the policy says anything about modules, which needs an
+    /// inventory with them.
+    pub fn checks_modules(&self) -> bool {
+        !self.allowed_module_directories.is_empty() || self.checks_signatures()
+    }
+
+    fn checks_signatures(&self) -> bool {
+        self.require_signature || !self.allowed_signers.is_empty()
+    }
+
+    /// Checks every snap-in in `inventory`, and the modules it uses.
+    /// Signatures are read from the module files on this machine.
+    pub fn evaluate(&self, inventory: &Inventory) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let allowed_clsids: Vec<String> = self.allowed_clsids.iter().filter_map(|c| normalize_clsid(c)).collect();
+        let mut signatures = HashMap::new();
+
+        for snapin in &inventory.snapins {
+            let mut violation = |message: String| {
+                violations.push(Violation { clsid: snapin.clsid.clone(), name: snapin.get_name().to_string(), message });
+            };
+
+            let clsid = normalize_clsid(&snapin.clsid).unwrap_or(snapin.clsid.clone());
+            if !allowed_clsids.is_empty() && !allowed_clsids.contains(&clsid) {
+                violation("not an allowed snap-in".to_string());
+            }
+
+            let provider = snapin.get_provider();
+            if !self.required_providers.is_empty() && !self.required_providers.iter().any(|p| p.eq_ignore_ascii_case(provider)) {
+                violation(match provider {
+                    "" => "has no provider".to_string(),
+                    provider => format!("provider '{}' is not a required provider", provider),
+                });
+            }
+
+            for module in inventory.modules_of(&snapin.clsid) {
+                if !self.allowed_module_directories.is_empty() && !self.allowed_module_directories.iter().any(|d| in_directory(&module.path, d)) {
+                    violation(format!("{} is outside the allowed module directories", module.path));
+                }
+
+                if self.checks_signatures() {
+                    // Modules are often shared, so each is only read once
+                    let signature = signatures
+                        .entry(module.path.to_lowercase())
+                        .or_insert_with(|| authenticode::read(Path::new(&module.path)).map_err(|e| e.to_string()));
+                    if let Some(message) = self.check_signature(signature) {
+                        violation(format!("{} {}", module.path, message));
+                    }
+                }
+            }
+        }
+
+        violations
+    }
+
+    fn check_signature(&self, signature: &Result<Option<Signature>, String>) -> Option<String> {
+        let signature = match signature {
+            Ok(Some(signature)) => signature,
+            Ok(None) => return Some("has no embedded signature".to_string()),
+            Err(e) => return Some(format!("can't be checked: {}", e)),
+        };
+
+        match signature.digest_matches {
+            Some(true) => {}
+            Some(false) => return Some("doesn't match its signature".to_string()),
+            None => return Some(format!("is signed with {}, which can't be checked", signature.digest_algorithm)),
+        }
+
+        let subject = signature.signer.as_deref().unwrap_or_default();
+        let common_name = common_name(subject);
+        let allowed = self.allowed_signers.is_empty()
+            || self.allowed_signers.iter().any(|s| s.eq_ignore_ascii_case(common_name) || s.eq_ignore_ascii_case(subject));
+        match allowed {
+            true => None,
+            false => Some(format!("is signed by '{}', which is not an allowed signer", subject)),
+        }
+    }
+}
+
+// The CN of a subject like `CN=Contoso, Inc., O=Contoso, Inc., C=US`, where
+// only the parts that start with an attribute type start a new attribute
+fn common_name(subject: &str) -> &str {
+    let mut start = None;
+    let mut offset = 0;
+    for part in subject.split(", ") {
+        let is_attribute = part.split_once('=').is_some_and(|(kind, _)| !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphanumeric() || c == '.'));
+        match (start, is_attribute) {
+            (None, true) if part.starts_with("CN=") => start = Some(offset + 3),
+            (Some(start), true) => return &subject[start..offset - 2],
+            _ => {}
+        }
+        offset += part.len() + 2;
+    }
+    start.map_or("", |start| &subject[start..])
+}
+
+// Whether `path` is in `directory` or below it, ignoring case and trailing
+// separators
+fn in_directory(path: &str, directory: &str) -> bool {
+    let normalize = |s: &str| s.replace('/', "\\").trim_end_matches('\\').to_lowercase();
+    let directory = normalize(&module_path(directory).to_string_lossy());
+    normalize(path).strip_prefix(&directory).is_some_and(|rest| rest.starts_with('\\'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashes::ModuleHashes;
+    use crate::snapin::MMCSnapIn;
+
+    fn snapin(clsid: &str, name: &str, provider: &str) -> MMCSnapIn {
+        MMCSnapIn {
+            clsid: clsid.into(),
+            namestring: Some(name.into()),
+            providerstringindirect: Some(provider.into()).filter(|p: &String| !p.is_empty()),
+            ..Default::default()
+        }
+    }
+
+    fn module(path: &str, clsids: &[&str]) -> ModuleHashes {
+        ModuleHashes { path: path.into(), snapins: clsids.iter().map(|c| c.to_string()).collect(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_parse() {
+        let policy = Policy::parse_toml(r#"
+            allowed_clsids = ["{58221C67-EA27-11CF-ADCF-00AA00A80033}"]
+            allowed_module_directories = ['C:\Windows\System32']
+        "#).unwrap();
+        assert_eq!(policy.allowed_module_directories, vec![r"C:\Windows\System32"]);
+        assert!(policy.checks_modules());
+
+        let policy = Policy::parse_json(r#"{"required_providers": ["Microsoft Corporation"]}"#).unwrap();
+        assert!(!policy.checks_modules());
+
+        assert!(Policy::parse_toml("allowed_snapins = []").is_err());
+    }
+
+    #[test]
+    fn test_common_name() {
+        assert_eq!(common_name("CN=Microsoft Windows, O=Microsoft Corporation, C=US"), "Microsoft Windows");
+        assert_eq!(common_name("CN=Contoso, Inc., O=Contoso, Inc., C=US"), "Contoso, Inc.");
+        assert_eq!(common_name("O=Contoso, CN=Contoso Signing"), "Contoso Signing");
+        assert_eq!(common_name("O=Contoso"), "");
+    }
+
+    #[test]
+    fn test_in_directory() {
+        assert!(in_directory(r"C:\Windows\System32\compmgmt.dll", r"c:\windows\system32\"));
+        assert!(in_directory(r"C:\Windows\System32\en-US\compmgmt.dll", r"C:\Windows\System32"));
+        assert!(!in_directory(r"C:\Windows\System32evil\compmgmt.dll", r"C:\Windows\System32"));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        const COMPUTER_MANAGEMENT: &str = "{58221C67-EA27-11CF-ADCF-00AA00A80033}";
+        const CONTOSO: &str = "{11111111-2222-3333-4444-555555555555}";
+
+        let inventory = Inventory {
+            snapins: vec![
+                snapin(COMPUTER_MANAGEMENT, "Computer Management", "Microsoft Corporation"),
+                snapin(&CONTOSO.to_lowercase(), "Contoso", ""),
+            ],
+            modules: vec![
+                module(r"C:\Windows\System32\mycomput.dll", &[COMPUTER_MANAGEMENT]),
+                module(r"C:\Tools\contoso.dll", &[CONTOSO]),
+            ],
+        };
+        let policy = Policy {
+            allowed_clsids: vec![COMPUTER_MANAGEMENT.to_lowercase()],
+            required_providers: vec!["microsoft corporation".into()],
+            allowed_module_directories: vec![r"C:\Windows\System32".into()],
+            ..Default::default()
+        };
+
+        let violations: Vec<String> = policy.evaluate(&inventory).iter().map(ToString::to_string).collect();
+        assert_eq!(violations, vec![
+            r"{11111111-2222-3333-4444-555555555555}  Contoso: not an allowed snap-in",
+            r"{11111111-2222-3333-4444-555555555555}  Contoso: has no provider",
+            r"{11111111-2222-3333-4444-555555555555}  Contoso: C:\Tools\contoso.dll is outside the allowed module directories",
+        ]);
+    }
+}
@@ -0,0 +1,440 @@
+use std::fmt::{self, Display};
+
+use crate::snapin::MMCSnapIn;
+
+/// The fields a query can test, with what they're read from.
+pub const FIELDS: [(&str, &str); 20] = [
+    ("name", "NameString, or the resolved NameStringIndirect"),
+    ("description", "Description, or the About object's"),
+    ("clsid", "the snap-in's CLSID"),
+    ("provider", "ProviderStringIndirect, or the About object's"),
+    ("version", "VersionStringIndirect, or the About object's"),
+    ("module", "the in-proc server, or the assembly of a managed snap-in"),
+    ("module_name", "ModuleName"),
+    ("application_base", "ApplicationBase"),
+    ("standalone", "whether it can be added on its own"),
+    ("managed", "whether it's written in .NET"),
+    ("blocked", "whether Group Policy keeps it from running"),
+    ("about", "whether its About object was resolved"),
+    ("about.description", "the About object's description"),
+    ("about.provider", "the About object's provider"),
+    ("about.version", "the About object's version"),
+    ("node_types", "the node types it publishes"),
+    ("extensions", "the CLSIDs and names of the snap-ins extending it"),
+    ("diagnostics", "what couldn't be read or resolved"),
+    ("servers", "every registered in-proc server path"),
+    ("values", "its registry values, as name=data"),
+];
+
+/// A parsed filter expression, like
+/// `standalone && provider ~ "Microsoft" && !about`.
+///
+/// A field on its own is true when it's set: a flag that's on, a string
+/// that isn't empty, a list with something in it. `==`, `!=`, `~` (contains),
+/// `!~`, `startswith` and `endswith` compare text without regard to case,
+/// and a list matches when any of its items does (`!=` and `!~` when none
+/// does). Combine tests with `&&`, `||`, `!` and parentheses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    Set(&'static str),
+    Compare(&'static str, Operator, String),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Equals,
+    NotEquals,
+    Contains,
+    NotContains,
+    StartsWith,
+    EndsWith,
+}
+
+/// Why a query couldn't be parsed, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// In characters from the start of the query.
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// The error under the query with a caret pointing at the problem.
+    pub fn show(&self, query: &str) -> String {
+        format!("{}\n{}^ {}", query, " ".repeat(self.position), self)
+    }
+}
+
+impl Query {
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { tokens: tokenize(s)?, next: 0, end: s.chars().count() };
+        let query = parser.or()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some((position, Token::RParen)) => Err(ParseError { position, message: "unmatched `)`".into() }),
+            Some((position, token)) => Err(ParseError { position, message: format!("expected `&&` or `||` before {}", token) }),
+        }
+    }
+
+    pub fn matches(&self, snapin: &MMCSnapIn) -> bool {
+        match self {
+            Query::Set(field) => values(snapin, field).iter().any(|v| !v.is_empty() && v != "false"),
+            Query::Compare(field, op, value) => {
+                let value = value.to_lowercase();
+                let test = |v: &String| {
+                    let v = v.to_lowercase();
+                    match op {
+                        Operator::Equals | Operator::NotEquals => v == value,
+                        Operator::Contains | Operator::NotContains => v.contains(&value),
+                        Operator::StartsWith => v.starts_with(&value),
+                        Operator::EndsWith => v.ends_with(&value),
+                    }
+                };
+                let any = values(snapin, field).iter().any(test);
+                match op {
+                    Operator::NotEquals | Operator::NotContains => !any,
+                    _ => any,
+                }
+            }
+            Query::Not(query) => !query.matches(snapin),
+            Query::And(a, b) => a.matches(snapin) && b.matches(snapin),
+            Query::Or(a, b) => a.matches(snapin) || b.matches(snapin),
+        }
+    }
+}
+
+/// Whether the text typed in a filter box is meant as a query rather than
+/// text to look for: it has an operator or a quote in it.
+pub fn looks_like_query(s: &str) -> bool {
+    s.contains(['&', '|', '!', '~', '=', '(', ')', '"'])
+        || s.split_whitespace().any(|w| w.eq_ignore_ascii_case("startswith") || w.eq_ignore_ascii_case("endswith"))
+}
+
+// What `field` says about `snapin`, as text; flags are "true" or "false"
+fn values(snapin: &MMCSnapIn, field: &str) -> Vec<String> {
+    let flag = |b: bool| vec![b.to_string()];
+    let text = |s: &str| vec![s.to_string()];
+    let optional = |s: &Option<String>| s.iter().cloned().collect();
+    let about = snapin.about.as_ref();
+
+    match field {
+        "name" => text(snapin.get_name()),
+        "description" => text(snapin.get_description()),
+        "clsid" => text(&snapin.clsid),
+        "provider" => text(snapin.get_provider()),
+        "version" => text(snapin.versionstringindirect.as_deref().or(about.and_then(|a| a.version.as_deref())).unwrap_or("")),
+        "module" => snapin.module().into_iter().collect(),
+        "module_name" => optional(&snapin.module_name),
+        "application_base" => optional(&snapin.application_base),
+        "standalone" => flag(snapin.standalone),
+        "managed" => flag(snapin.is_managed()),
+        "blocked" => flag(snapin.restriction.blocked),
+        "about" => flag(about.is_some()),
+        "about.description" => about.map(|a| optional(&a.description)).unwrap_or_default(),
+        "about.provider" => about.map(|a| optional(&a.provider)).unwrap_or_default(),
+        "about.version" => about.map(|a| optional(&a.version)).unwrap_or_default(),
+        "node_types" => snapin.node_types.clone(),
+        "extensions" => snapin.extensions.iter().flat_map(|e| [Some(e.clsid.clone()), e.name.clone()]).flatten().collect(),
+        "diagnostics" => snapin.diagnostics.clone(),
+        "servers" => snapin.servers.iter().map(|s| s.path.clone()).collect(),
+        "values" => snapin.registry_values.iter().map(|(name, data)| format!("{}={}", name, data)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Op(Operator),
+    Word(String),
+    Quoted(String),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
+            Token::Not => write!(f, "`!`"),
+            Token::Op(op) => write!(f, "`{}`", op),
+            Token::Word(word) => write!(f, "`{}`", word),
+            Token::Quoted(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operator::Equals => "==",
+            Operator::NotEquals => "!=",
+            Operator::Contains => "~",
+            Operator::NotContains => "!~",
+            Operator::StartsWith => "startswith",
+            Operator::EndsWith => "endswith",
+        })
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        let pair = |second: char| chars.get(start + 1) == Some(&second);
+        let token = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '&' if pair('&') => Token::And,
+            '|' if pair('|') => Token::Or,
+            '=' if pair('=') => Token::Op(Operator::Equals),
+            '!' if pair('=') => Token::Op(Operator::NotEquals),
+            '!' if pair('~') => Token::Op(Operator::NotContains),
+            '!' => Token::Not,
+            '~' => Token::Op(Operator::Contains),
+            c @ ('&' | '|' | '=') => {
+                let expected = match c { '&' => "&&", '|' => "||", _ => "==" };
+                return Err(ParseError { position: i, message: format!("expected `{}`", expected) });
+            }
+            '"' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(ParseError { position: start, message: "unterminated string".into() }),
+                        Some('"') => break,
+                        Some('\\') if i + 1 < chars.len() => {
+                            value.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&c) => {
+                            value.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                Token::Quoted(value)
+            }
+            _ => {
+                while i < chars.len() && !chars[i].is_whitespace() && !"()&|=!~\"".contains(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                tokens.push((start, match word.to_lowercase().as_str() {
+                    "startswith" => Token::Op(Operator::StartsWith),
+                    "endswith" => Token::Op(Operator::EndsWith),
+                    _ => Token::Word(word),
+                }));
+                continue;
+            }
+        };
+
+        i = match token {
+            Token::And | Token::Or | Token::Op(Operator::Equals | Operator::NotEquals | Operator::NotContains) => i + 2,
+            _ => i + 1,
+        };
+        tokens.push((start, token));
+    }
+
+    Ok(tokens)
+}
+
+// Recursive descent, loosest binding first: `||`, `&&`, `!`, then a field
+// test or a parenthesized query
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    // Where errors at the end of the query point
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<(usize, Token)> {
+        self.tokens.get(self.next).cloned()
+    }
+
+    fn take(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.next).is_some_and(|(_, t)| t == token);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.and()?;
+        while self.take(&Token::Or) {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, ParseError> {
+        let mut query = self.not()?;
+        while self.take(&Token::And) {
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+
+    fn not(&mut self) -> Result<Query, ParseError> {
+        match self.take(&Token::Not) {
+            true => Ok(Query::Not(Box::new(self.not()?))),
+            false => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Query, ParseError> {
+        let Some((position, token)) = self.peek() else {
+            return Err(ParseError { position: self.end, message: "expected a field name".into() });
+        };
+        self.next += 1;
+
+        let field = match token {
+            Token::LParen => {
+                let query = self.or()?;
+                return match self.take(&Token::RParen) {
+                    true => Ok(query),
+                    false => Err(ParseError { position, message: "unclosed `(`".into() }),
+                };
+            }
+            Token::Word(word) => field(&word).ok_or_else(|| ParseError {
+                position,
+                message: format!("unknown field `{}`; fields are {}", word, FIELDS.map(|(name, _)| name).join(", ")),
+            })?,
+            token => return Err(ParseError { position, message: format!("expected a field name, not {}", token) }),
+        };
+
+        let Some((_, Token::Op(op))) = self.peek() else {
+            return Ok(Query::Set(field));
+        };
+        self.next += 1;
+        match self.peek() {
+            Some((_, Token::Word(value) | Token::Quoted(value))) => {
+                self.next += 1;
+                Ok(Query::Compare(field, op, value))
+            }
+            Some((position, token)) => Err(ParseError { position, message: format!("expected a value after `{}`, not {}", op, token) }),
+            None => Err(ParseError { position: self.end, message: format!("expected a value after `{}`", op) }),
+        }
+    }
+}
+
+fn field(name: &str) -> Option<&'static str> {
+    FIELDS.iter().map(|(field, _)| *field).find(|field| field.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapin::{MMCServer, MMCSnapInAbout};
+
+    // MMCSnapInAbout owns handles on Windows, so it can't be built with
+    // `..Default::default()`
+    #[allow(clippy::field_reassign_with_default)]
+    fn snapins() -> Vec<MMCSnapIn> {
+        let mut about = MMCSnapInAbout::default();
+        about.provider = Some("Microsoft Corporation".into());
+
+        vec![
+            MMCSnapIn {
+                clsid: "{E355E538-1C2E-11D0-8C37-00C04FD8FE93}".into(),
+                namestring: Some("Active Directory Users and Computers".into()),
+                providerstringindirect: Some("Microsoft Corporation".into()),
+                standalone: true,
+                servers: vec![MMCServer {
+                    clsid: "{E355E538-1C2E-11D0-8C37-00C04FD8FE93}".into(),
+                    root: r"HKEY_LOCAL_MACHINE\SOFTWARE\Classes\CLSID".into(),
+                    path: r"%SystemRoot%\System32\dsadmin.dll".into(),
+                    effective: true,
+                }],
+                ..Default::default()
+            },
+            MMCSnapIn {
+                clsid: "{975797FC-4E2A-11D0-B702-00C04FD8DBF7}".into(),
+                namestring: Some("Event Viewer".into()),
+                standalone: true,
+                about: Some(about),
+                node_types: vec!["{7AB4A1FC-E403-11D0-9A97-00C04FD8DBF7}".into()],
+                ..Default::default()
+            },
+            MMCSnapIn {
+                clsid: "{11111111-2222-3333-4444-555555555555}".into(),
+                namestring: Some("Contoso \"Tools\"".into()),
+                diagnostics: vec!["About: not resolved".into()],
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn names(query: &str) -> Vec<String> {
+        let query = Query::parse(query).unwrap();
+        snapins().iter().filter(|s| query.matches(s)).map(|s| s.get_name().to_string()).collect()
+    }
+
+    #[test]
+    fn test_matches() {
+        assert_eq!(names(r#"standalone && provider ~ "Microsoft" && !about"#), ["Active Directory Users and Computers"]);
+        assert_eq!(names(r#"module endswith "DSADMIN.DLL""#), ["Active Directory Users and Computers"]);
+        assert_eq!(names("about.provider startswith microsoft"), ["Event Viewer"]);
+        assert_eq!(names("node_types == {7ab4a1fc-e403-11d0-9a97-00c04fd8dbf7}"), ["Event Viewer"]);
+        assert_eq!(names(r#"diagnostics || name ~ "\"tools\"""#), ["Contoso \"Tools\""]);
+        assert_eq!(names("!(standalone || diagnostics)"), Vec::<String>::new());
+        assert_eq!(names("diagnostics !~ resolved && name != \"event viewer\"").len(), 1);
+        assert_eq!(names("Standalone == true || STANDALONE == false").len(), 3);
+    }
+
+    #[test]
+    fn test_precedence() {
+        let set = |field| Box::new(Query::Set(field));
+        assert_eq!(Query::parse("!standalone && managed || blocked").unwrap(), Query::Or(
+            Box::new(Query::And(Box::new(Query::Not(set("standalone"))), set("managed"))),
+            set("blocked"),
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |query: &str| Query::parse(query).unwrap_err().to_string();
+
+        assert!(error("standalone && provdier ~ x").starts_with("unknown field `provdier`; fields are name, description,"));
+        assert_eq!(error("standalone & about"), "expected `&&` at column 12");
+        assert_eq!(error("provider ~"), "expected a value after `~` at column 11");
+        assert_eq!(error("name == \"Event"), "unterminated string at column 9");
+        assert_eq!(error("(standalone || about"), "unclosed `(` at column 1");
+        assert_eq!(error("standalone about"), "expected `&&` or `||` before `about` at column 12");
+        assert_eq!(error("standalone)"), "unmatched `)` at column 11");
+        assert_eq!(error(""), "expected a field name at column 1");
+        assert_eq!(Query::parse("name ~").unwrap_err().show("name ~"), "name ~\n      ^ expected a value after `~` at column 7");
+    }
+
+    #[test]
+    fn test_looks_like_query() {
+        assert!(!looks_like_query("event viewer"));
+        assert!(looks_like_query("standalone && about"));
+        assert!(looks_like_query("module endswith dsadmin.dll"));
+    }
+}
@@ -0,0 +1,263 @@
+use std::{fmt::Write, str::FromStr};
+
+use serde::Deserialize;
+
+use crate::nsi::IndirectString;
+use crate::snapin::{normalize_clsid, NODETYPES_KEY, SNAPINS_KEY};
+use crate::source::RegValue;
+
+const CLASSES_KEY: &str = r"HKEY_LOCAL_MACHINE\SOFTWARE\Classes\CLSID";
+
+pub const EXTENSION_KINDS: [&str; 6] = ["NameSpace", "ContextMenu", "PropertySheet", "Toolbar", "Task", "View"];
+
+/// A snap-in's registration, as written by its author. Read from JSON:
+///
+/// ```text
+/// {
+///     "clsid": "{11111111-2222-3333-4444-555555555555}",
+///     "about": "{11111111-2222-3333-4444-555555555556}",
+///     "server": "%ProgramFiles%\\Contoso\\contoso.dll",
+///     "name_string": "Contoso Manager",
+///     "name_string_indirect": "@%ProgramFiles%\\Contoso\\contoso.dll,-101",
+///     "standalone": true,
+///     "node_types": ["{11111111-2222-3333-4444-555555555557}"],
+///     "extensions": [
+///         { "node_type": "{476E6449-AAFF-11D0-B944-00C04FD8D5B0}", "kind": "NameSpace" }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SnapInRegistration {
+    pub clsid: String,
+    #[serde(default)]
+    pub about: Option<String>,
+    /// The in-proc server DLL registered for the snap-in and About CLSIDs.
+    pub server: String,
+    #[serde(default = "default_threading_model")]
+    pub threading_model: String,
+    #[serde(default)]
+    pub name_string: Option<String>,
+    #[serde(default)]
+    pub name_string_indirect: Option<String>,
+    #[serde(default)]
+    pub provider_string_indirect: Option<String>,
+    #[serde(default)]
+    pub version_string_indirect: Option<String>,
+    #[serde(default)]
+    pub standalone: bool,
+    /// Node types the snap-in itself creates.
+    #[serde(default)]
+    pub node_types: Vec<String>,
+    /// Other snap-ins' node types this one extends.
+    #[serde(default)]
+    pub extensions: Vec<ExtensionRegistration>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtensionRegistration {
+    pub node_type: String,
+    pub kind: String,
+    /// Shown for the extension in the node type's registration; defaults to
+    /// the snap-in's name.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+fn default_threading_model() -> String {
+    "Apartment".to_string()
+}
+
+impl SnapInRegistration {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+
+    /// The registration as the bytes of a `.reg` file, encoded the way its
+    /// "Version 5.00" header promises: UTF-16LE with a byte order mark and
+    /// CRLF line ends.
+    pub fn to_reg_file(&self) -> Result<Vec<u8>, String> {
+        let text = self.to_reg()?.replace('\n', "\r\n");
+        Ok([0xFF, 0xFE].into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect())
+    }
+
+    /// Writes the registration as a `.reg` file in the layout `snapin::get_snapins`
+    /// reads back.
+    pub fn to_reg(&self) -> Result<String, String> {
+        let clsid = parse_clsid(&self.clsid)?;
+        let about = self.about.as_deref().map(parse_clsid).transpose()?;
+        let name = self.name_string.clone().unwrap_or_else(|| clsid.clone());
+
+        let mut out = String::from("Windows Registry Editor Version 5.00\n");
+
+        let mut snapin_values = Vec::new();
+        if let Some(name) = &self.name_string {
+            snapin_values.push(("NameString", RegValue::String(name.clone())));
+        }
+        for (value, indirect) in [
+            ("NameStringIndirect", &self.name_string_indirect),
+            ("ProviderStringIndirect", &self.provider_string_indirect),
+            ("VersionStringIndirect", &self.version_string_indirect),
+        ] {
+            if let Some(indirect) = indirect {
+                IndirectString::from_str(indirect).map_err(|e| format!("{}: {}", value, e))?;
+                snapin_values.push((value, RegValue::String(indirect.clone())));
+            }
+        }
+        if let Some(about) = &about {
+            snapin_values.push(("About", RegValue::String(about.clone())));
+        }
+
+        let snapin_key = format!("{}\\{}", SNAPINS_KEY, clsid);
+        key(&mut out, &snapin_key, &snapin_values);
+        if self.standalone {
+            key(&mut out, &format!("{}\\StandAlone", snapin_key), &[]);
+        }
+
+        let node_types = self.node_types.iter().map(|n| parse_clsid(n)).collect::<Result<Vec<_>, _>>()?;
+        for node_type in &node_types {
+            key(&mut out, &format!("{}\\NodeTypes\\{}", snapin_key, node_type), &[]);
+        }
+        for node_type in &node_types {
+            key(&mut out, &format!("{}\\{}", NODETYPES_KEY, node_type), &[]);
+        }
+
+        for extension in &self.extensions {
+            let node_type = parse_clsid(&extension.node_type)?;
+            let kind = EXTENSION_KINDS
+                .iter()
+                .find(|k| k.eq_ignore_ascii_case(&extension.kind))
+                .ok_or(format!("unknown extension kind '{}'", extension.kind))?;
+            let extension_name = extension.name.clone().unwrap_or_else(|| name.clone());
+            key(&mut out, &format!("{}\\{}\\Extensions\\{}", NODETYPES_KEY, node_type, kind), &[(clsid.as_str(), RegValue::String(extension_name))]);
+        }
+
+        // COM registration for the snap-in and its About object
+        let mut servers = vec![(clsid.clone(), name.clone())];
+        if let Some(about) = about.filter(|a| *a != clsid) {
+            servers.push((about, format!("{} About", name)));
+        }
+        // Environment variables in the path are only expanded from REG_EXPAND_SZ
+        let server = match self.server.contains('%') {
+            true => RegValue::ExpandString(self.server.clone()),
+            false => RegValue::String(self.server.clone()),
+        };
+        for (server_clsid, description) in servers {
+            key(&mut out, &format!("{}\\{}", CLASSES_KEY, server_clsid), &[("", RegValue::String(description))]);
+            key(&mut out, &format!("{}\\{}\\InprocServer32", CLASSES_KEY, server_clsid), &[
+                ("", server.clone()),
+                ("ThreadingModel", RegValue::String(self.threading_model.clone())),
+            ]);
+        }
+
+        Ok(out)
+    }
+}
+
+fn parse_clsid(s: &str) -> Result<String, String> {
+    normalize_clsid(s).ok_or(format!("invalid CLSID '{}'", s))
+}
+
+fn key(out: &mut String, path: &str, values: &[(&str, RegValue)]) {
+    let _ = writeln!(out, "\n[{}]", path);
+    for (name, data) in values {
+        let name = match *name {
+            "" => "@".to_string(),
+            name => quote(name),
+        };
+        let _ = writeln!(out, "{}={}", name, value(data));
+    }
+}
+
+fn value(data: &RegValue) -> String {
+    match data {
+        RegValue::String(s) => quote(s),
+        RegValue::Dword(n) => format!("dword:{:08x}", n),
+        RegValue::ExpandString(s) => hex(2, &s.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect::<Vec<_>>()),
+        RegValue::MultiString(strings) => {
+            let wide = strings.iter().flat_map(|s| s.encode_utf16().chain([0])).chain([0]);
+            hex(7, &wide.flat_map(u16::to_le_bytes).collect::<Vec<_>>())
+        }
+        RegValue::Qword(n) => hex(0xb, &n.to_le_bytes()),
+        RegValue::Binary(bytes) => hex(3, bytes),
+    }
+}
+
+fn hex(kind: u32, bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    match kind {
+        3 => format!("hex:{}", bytes.join(",")),
+        kind => format!("hex({:x}):{}", kind, bytes.join(",")),
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapin::{get_snapins, NoResolver};
+    use crate::source::{RegFile, RegistrySource};
+
+    const DESCRIPTION: &str = r#"{
+        "clsid": "{11111111-2222-3333-4444-555555555555}",
+        "about": "{11111111-2222-3333-4444-555555555556}",
+        "server": "%ProgramFiles%\\Contoso\\contoso.dll",
+        "name_string": "Contoso \"Manager\"",
+        "name_string_indirect": "@%ProgramFiles%\\Contoso\\contoso.dll,-101",
+        "standalone": true,
+        "node_types": ["{11111111-2222-3333-4444-555555555557}"],
+        "extensions": [
+            { "node_type": "{476e6449-aaff-11d0-b944-00c04fd8d5b0}", "kind": "namespace" }
+        ]
+    }"#;
+
+    #[test]
+    fn test_round_trip() {
+        let reg = SnapInRegistration::parse(DESCRIPTION).unwrap().to_reg().unwrap();
+        let file = RegFile::parse(&reg).unwrap();
+
+        let snapins = get_snapins(&file, &NoResolver, 1, &[]).unwrap();
+        assert_eq!(snapins.len(), 1);
+        let snapin = &snapins[0];
+        assert_eq!(snapin.clsid, "{11111111-2222-3333-4444-555555555555}");
+        assert_eq!(snapin.namestring.as_deref(), Some("Contoso \"Manager\""));
+        assert!(snapin.registry_values.contains(&("About".into(), "{11111111-2222-3333-4444-555555555556}".into())));
+        assert!(snapin.standalone);
+        assert_eq!(snapin.node_types, vec!["{11111111-2222-3333-4444-555555555557}"]);
+
+        let extensions = file.values(&format!(r"{}\{{476E6449-AAFF-11D0-B944-00C04FD8D5B0}}\Extensions\NameSpace", NODETYPES_KEY)).unwrap();
+        assert_eq!(extensions[0].0, "{11111111-2222-3333-4444-555555555555}");
+
+        let server = file.values(&format!(r"{}\{{11111111-2222-3333-4444-555555555556}}\InprocServer32", CLASSES_KEY)).unwrap();
+        assert_eq!(server[0], (String::new(), RegValue::ExpandString(r"%ProgramFiles%\Contoso\contoso.dll".into())));
+    }
+
+    #[test]
+    fn test_reg_file_encoding() {
+        let bytes = SnapInRegistration::parse(DESCRIPTION).unwrap().to_reg_file().unwrap();
+        assert!(bytes.starts_with(&[0xFF, 0xFE, b'W', 0, b'i', 0]));
+        assert!(bytes.windows(4).any(|w| w == [b'\r', 0, b'\n', 0]));
+
+        let path = std::env::temp_dir().join(format!("enum-snapins-register-{}.reg", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let file = RegFile::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(get_snapins(&file.unwrap(), &NoResolver, 1, &[]).unwrap()[0].namestring.as_deref(), Some("Contoso \"Manager\""));
+    }
+
+    #[test]
+    fn test_invalid_description() {
+        let invalid = DESCRIPTION.replace("-101", "x");
+        let error = SnapInRegistration::parse(&invalid).unwrap().to_reg().unwrap_err();
+        assert!(error.starts_with("NameStringIndirect"), "{}", error);
+
+        let invalid = DESCRIPTION.replace("namespace", "Sidebar");
+        assert_eq!(SnapInRegistration::parse(&invalid).unwrap().to_reg().unwrap_err(), "unknown extension kind 'Sidebar'");
+
+        assert!(SnapInRegistration::parse(r#"{"clsid": "{11111111-2222-3333-4444-555555555555}"}"#).is_err());
+    }
+}
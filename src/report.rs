@@ -0,0 +1,250 @@
+use std::fmt::Write;
+
+use crate::snapin::MMCSnapIn;
+use crate::summary::Summary;
+
+// Icons only come from About objects, which are only resolved on Windows
+#[cfg_attr(not(windows), allow(dead_code))]
+mod png;
+
+const NO_PROVIDER: &str = "(no provider)";
+
+/// Renders `snapins` as Markdown: a summary, then a table per provider.
+pub fn markdown(snapins: &[MMCSnapIn]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Snap-in inventory\n\n{}", summary(snapins));
+
+    for (provider, group) in by_provider(snapins) {
+        let _ = writeln!(out, "\n## {}\n", escape_markdown(provider));
+        let _ = writeln!(out, "| Name | CLSID | Version | Standalone | Extensions | Diagnostics |");
+        let _ = writeln!(out, "| --- | --- | --- | --- | --- | --- |");
+        for snapin in group {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} |",
+                escape_markdown(snapin.get_name()),
+                escape_markdown(&snapin.clsid),
+                escape_markdown(version(snapin)),
+                if snapin.standalone { "Yes" } else { "No" },
+                snapin.extensions.len(),
+                escape_markdown(&snapin.diagnostics.join("; ")),
+            );
+        }
+    }
+
+    out
+}
+
+/// Renders `snapins` as an HTML page that needs nothing else: icons are
+/// embedded, extension lists fold away, and snap-ins with diagnostics stand
+/// out.
+pub fn html(snapins: &[MMCSnapIn]) -> String {
+    let mut out = String::from(concat!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Snap-in inventory</title>\n<style>\n",
+        "body { font-family: \"Segoe UI\", sans-serif; margin: 2em; }\n",
+        "table { border-collapse: collapse; width: 100%; }\n",
+        "th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }\n",
+        "tr.diagnostics { background: #fff3cd; }\n",
+        "tr.diagnostics ul { color: #8a4b00; }\n",
+        "td ul { margin: 0; padding-left: 1.2em; }\n",
+        "img { width: 32px; height: 32px; }\n",
+        "</style>\n</head>\n<body>\n<h1>Snap-in inventory</h1>\n",
+    ));
+    let _ = writeln!(out, "<p>{}</p>", escape_html(&summary(snapins)));
+
+    for (provider, group) in by_provider(snapins) {
+        let _ = writeln!(out, "<h2>{}</h2>\n<table>", escape_html(provider));
+        let _ = writeln!(out, "<tr><th></th><th>Name</th><th>CLSID</th><th>Version</th><th>Standalone</th><th>Extensions</th><th>Diagnostics</th></tr>");
+        for snapin in group {
+            let class = if snapin.diagnostics.is_empty() { "" } else { " class=\"diagnostics\"" };
+            let icon = icon(snapin).map(|png| format!("<img alt=\"\" src=\"data:image/png;base64,{}\">", png::base64(&png))).unwrap_or_default();
+            let extensions = match snapin.extensions.len() {
+                0 => String::new(),
+                n => {
+                    let items: String = snapin.extensions.iter().map(|e| {
+                        format!(
+                            "<li>{} {} {} (on {})</li>",
+                            escape_html(&e.kind),
+                            escape_html(&e.clsid),
+                            escape_html(e.name.as_deref().unwrap_or("")),
+                            escape_html(&e.node_type),
+                        )
+                    }).collect();
+                    format!("<details><summary>{} extension{}</summary><ul>{}</ul></details>", n, if n == 1 { "" } else { "s" }, items)
+                }
+            };
+            let diagnostics = match snapin.diagnostics.is_empty() {
+                true => String::new(),
+                false => format!("<ul>{}</ul>", snapin.diagnostics.iter().map(|d| format!("<li>{}</li>", escape_html(d))).collect::<String>()),
+            };
+
+            let _ = writeln!(
+                out,
+                "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                class,
+                icon,
+                escape_html(snapin.get_name()),
+                escape_html(&snapin.clsid),
+                escape_html(version(snapin)),
+                if snapin.standalone { "Yes" } else { "No" },
+                extensions,
+                diagnostics,
+            );
+        }
+        out.push_str("</table>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn summary(snapins: &[MMCSnapIn]) -> String {
+    let summary = Summary::new(snapins);
+    format!(
+        "{} snap-ins from {} providers: {} standalone, {} extensions only, {} native, {} managed, {} with diagnostics, {} blocked by policy.",
+        summary.total,
+        summary.by_provider.len(),
+        summary.standalone,
+        summary.extension_only,
+        summary.native,
+        summary.managed,
+        summary.with_diagnostics,
+        summary.blocked,
+    )
+}
+
+// Providers by name, those with none last, and their snap-ins by name
+fn by_provider(snapins: &[MMCSnapIn]) -> Vec<(&str, Vec<&MMCSnapIn>)> {
+    let mut groups: Vec<(&str, Vec<&MMCSnapIn>)> = Vec::new();
+    for snapin in snapins {
+        let provider = match snapin.get_provider() {
+            "" => NO_PROVIDER,
+            provider => provider,
+        };
+        match groups.iter_mut().find(|(p, _)| p.eq_ignore_ascii_case(provider)) {
+            Some((_, group)) => group.push(snapin),
+            None => groups.push((provider, vec![snapin])),
+        }
+    }
+
+    groups.sort_by_key(|(p, _)| (*p == NO_PROVIDER, p.to_lowercase()));
+    for (_, group) in &mut groups {
+        group.sort_by_key(|s| s.get_name().to_lowercase());
+    }
+    groups
+}
+
+fn version(snapin: &MMCSnapIn) -> &str {
+    snapin.versionstringindirect.as_deref().or(snapin.about.as_ref().and_then(|a| a.version.as_deref())).unwrap_or("")
+}
+
+#[cfg(windows)]
+fn icon(snapin: &MMCSnapIn) -> Option<Vec<u8>> {
+    let (width, height, pixels) = snapin.about.as_ref()?.rgba()?;
+    Some(png::png(width, height, &pixels))
+}
+
+#[cfg(not(windows))]
+fn icon(_snapin: &MMCSnapIn) -> Option<Vec<u8>> {
+    None
+}
+
+// Wikis render HTML in Markdown, so that's escaped too
+fn escape_markdown(s: &str) -> String {
+    escape_html(s).replace('\\', "\\\\").replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapin::MMCExtension;
+
+    fn snapins() -> Vec<MMCSnapIn> {
+        vec![
+            MMCSnapIn {
+                clsid: "{975797FC-4E2A-11D0-B702-00C04FD8DBF7}".into(),
+                namestring: Some("Event Viewer".into()),
+                providerstringindirect: Some("Microsoft Corporation".into()),
+                standalone: true,
+                ..Default::default()
+            },
+            MMCSnapIn {
+                clsid: "{11111111-2222-3333-4444-555555555555}".into(),
+                namestring: Some("Contoso | Tools".into()),
+                diagnostics: vec!["About {11111111-2222-3333-4444-555555555556}: <not registered>".into()],
+                ..Default::default()
+            },
+            MMCSnapIn {
+                clsid: "{58221C67-EA27-11CF-ADCF-00AA00A80033}".into(),
+                namestring: Some("Computer Management".into()),
+                providerstringindirect: Some("Microsoft Corporation".into()),
+                versionstringindirect: Some("1.0".into()),
+                standalone: true,
+                extensions: vec![MMCExtension {
+                    node_type: "{476E6446-AAFF-11D0-B944-00C04FD8D5B0}".into(),
+                    kind: "NameSpace".into(),
+                    clsid: "{975797FC-4E2A-11D0-B702-00C04FD8DBF7}".into(),
+                    name: Some("Event Viewer".into()),
+                }],
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(markdown(&snapins()), r"# Snap-in inventory
+
+3 snap-ins from 2 providers: 2 standalone, 1 extensions only, 3 native, 0 managed, 1 with diagnostics, 0 blocked by policy.
+
+## Microsoft Corporation
+
+| Name | CLSID | Version | Standalone | Extensions | Diagnostics |
+| --- | --- | --- | --- | --- | --- |
+| Computer Management | {58221C67-EA27-11CF-ADCF-00AA00A80033} | 1.0 | Yes | 1 |  |
+| Event Viewer | {975797FC-4E2A-11D0-B702-00C04FD8DBF7} |  | Yes | 0 |  |
+
+## (no provider)
+
+| Name | CLSID | Version | Standalone | Extensions | Diagnostics |
+| --- | --- | --- | --- | --- | --- |
+| Contoso \| Tools | {11111111-2222-3333-4444-555555555555} |  | No | 0 | About {11111111-2222-3333-4444-555555555556}: &lt;not registered&gt; |
+");
+    }
+
+    #[test]
+    fn test_html() {
+        let html = html(&snapins());
+        assert!(html.contains("<h2>Microsoft Corporation</h2>"));
+        assert!(html.contains("<details><summary>1 extension</summary><ul><li>NameSpace {975797FC-4E2A-11D0-B702-00C04FD8DBF7} Event Viewer (on {476E6446-AAFF-11D0-B944-00C04FD8D5B0})</li></ul></details>"));
+        assert!(html.contains("<tr class=\"diagnostics\"><td></td><td>Contoso | Tools</td>"));
+        assert!(html.contains("<li>About {11111111-2222-3333-4444-555555555556}: &lt;not registered&gt;</li>"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn test_registry_text_escaped() {
+        let snapins = [MMCSnapIn {
+            clsid: "<script>|</script>".into(),
+            extensions: vec![MMCExtension {
+                node_type: "<b>".into(),
+                kind: "<i>".into(),
+                clsid: "a&b".into(),
+                name: None,
+            }],
+            ..Default::default()
+        }];
+
+        let html = html(&snapins);
+        assert!(!html.contains("<script>") && !html.contains("<b>") && !html.contains("<i>"));
+        assert!(html.contains("<td>&lt;script&gt;|&lt;/script&gt;</td>"));
+        assert!(html.contains("<li>&lt;i&gt; a&amp;b  (on &lt;b&gt;)</li>"));
+        let markdown = markdown(&snapins);
+        assert!(!markdown.contains("<script>"));
+        assert!(markdown.contains(r"| &lt;script&gt;\|&lt;/script&gt; |"));
+    }
+}
@@ -1,26 +1,38 @@
-use std::{error::Error, str::FromStr};
-
-use log::trace;
-
-use registry::{self as reg, Hive, RegKey, Security};
-use windows::core::{IUnknown, Interface, GUID, PCWSTR, PWSTR};
-use windows::Win32::Foundation::COLORREF;
-use windows::Win32::UI::WindowsAndMessaging::{CopyIcon, CopyImage, DestroyIcon, IMAGE_BITMAP, IMAGE_FLAGS};
-use windows::Win32::{
-    System::{
-        Com::{
-            CoCreateInstance, CoTaskMemFree, CLSCTX_INPROC_SERVER
-        },
-        LibraryLoader::LoadLibraryW,
-        Mmc::ISnapinAbout
-    },
-    UI::WindowsAndMessaging::{LoadStringW, HICON},
-};
-use windows::Win32::Graphics::Gdi::{self, DeleteObject, HBITMAP};
-
-use crate::nsi;
-
-#[derive(Clone, Default)]
+use std::{collections::BTreeMap, error::Error, fmt::{self, Display}, str::FromStr, sync::atomic::{AtomicUsize, Ordering}, thread};
+
+use serde::{Deserialize, Serialize};
+
+use crate::nsi::IndirectString;
+use crate::source::RegistrySource;
+
+// Only used to resolve the live registry, so only on Windows
+#[cfg_attr(not(windows), allow(dead_code))]
+pub mod cache;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod isolated;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod module;
+mod restriction;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod safe;
+#[cfg(windows)]
+mod win32;
+
+pub use module::{inproc_server, inproc_servers, module_path, MMCServer};
+pub use restriction::MMCRestriction;
+#[cfg(windows)]
+pub use cache::CachingResolver;
+#[cfg(windows)]
+pub use isolated::{worker_answer, IsolatedResolver};
+#[cfg(windows)]
+pub use safe::StaticResolver;
+#[cfg(windows)]
+pub use win32::{MMCSnapInImage, NativeResolver};
+
+pub const SNAPINS_KEY: &str = r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns";
+pub const NODETYPES_KEY: &str = r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\NodeTypes";
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct MMCSnapIn {
     pub clsid: String,
     pub about: Option<MMCSnapInAbout>,
@@ -33,16 +45,65 @@ pub struct MMCSnapIn {
     pub versionstringindirect: Option<String>,
     pub application_base: Option<String>,
     pub module_name: Option<String>,
+    pub node_types: Vec<String>,
+    pub extensions: Vec<MMCExtension>,
+    /// Every value under the snap-in's key, as (name, data)
+    pub registry_values: Vec<(String, String)>,
+    /// Problems hit while resolving the registration, e.g. a string resource
+    /// or About object that couldn't be loaded
+    pub diagnostics: Vec<String>,
+    /// The indirect strings resolved in other languages, by language tag
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub translations: BTreeMap<String, MMCSnapInStrings>,
+    /// Whether Group Policy lets the snap-in run
+    #[serde(default)]
+    pub restriction: MMCRestriction,
+    /// The in-proc servers registered for the snap-in and its About object,
+    /// per-user and machine-wide
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub servers: Vec<MMCServer>,
+}
+
+/// A snap-in's indirect strings in one language.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MMCSnapInStrings {
+    pub name: Option<String>,
+    pub provider: Option<String>,
+    pub version: Option<String>,
+}
+
+impl MMCSnapInStrings {
+    const VALUES: [&'static str; 3] = ["NameStringIndirect", "ProviderStringIndirect", "VersionStringIndirect"];
+
+    // The field the indirect string value `value` resolves into
+    fn field(&mut self, value: &str) -> Option<&mut Option<String>> {
+        match value {
+            "NameStringIndirect" => Some(&mut self.name),
+            "ProviderStringIndirect" => Some(&mut self.provider),
+            "VersionStringIndirect" => Some(&mut self.version),
+            _ => None,
+        }
+    }
+}
+
+/// A snap-in registered under `MMC\NodeTypes\{node_type}\Extensions\{kind}`
+/// to extend one of the node types another snap-in publishes.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MMCExtension {
+    pub node_type: String,
+    pub kind: String,
+    pub clsid: String,
+    pub name: Option<String>,
 }
 
 impl MMCSnapIn {
-    pub fn get_description<'a>(&'a self) -> &'a str {
+    pub fn get_description(&self) -> &str {
         if let Some(desc) = &self.description {
-            &desc
+            desc
         }
         else if let Some(about) = &self.about {
             if let Some(desc) = &about.description {
-                &desc
+                desc
             }
             else {
                 ""
@@ -54,312 +115,494 @@ impl MMCSnapIn {
 
     }
 
-    pub fn get_name<'a>(&'a self) -> &'a str {
+    pub fn get_name(&self) -> &str {
         if let Some(name) = &self.namestring {
-            &name
+            name
         }
         else if let Some(name) = &self.namestringindirect {
-            &name
+            name
         }
         else {
             ""
         }
 
     }
+
+    /// The indirect strings with no translation in one of `languages`, as
+    /// `language value`, e.g. `de-DE NameStringIndirect`.
+    pub fn missing_translations(&self, languages: &[String]) -> Vec<String> {
+        let mut missing = Vec::new();
+
+        for language in languages {
+            let mut strings = self.translations.get(language).cloned().unwrap_or_default();
+            for value in MMCSnapInStrings::VALUES {
+                let registered = self.registry_values.iter().any(|(name, _)| name == value);
+                if registered && strings.field(value).is_some_and(|s| s.is_none()) {
+                    missing.push(format!("{} {}", language, value));
+                }
+            }
+        }
+
+        missing
+    }
+
+    /// A copy with its About object's icon and images left out, see
+    /// `MMCSnapInAbout::text_only`.
+    pub fn text_only(&self) -> Self {
+        MMCSnapIn {
+            clsid: self.clsid.clone(),
+            about: self.about.as_ref().map(MMCSnapInAbout::text_only),
+            namestring: self.namestring.clone(),
+            description: self.description.clone(),
+            namestringindirect: self.namestringindirect.clone(),
+            standalone: self.standalone,
+            providerstringindirect: self.providerstringindirect.clone(),
+            versionstringindirect: self.versionstringindirect.clone(),
+            application_base: self.application_base.clone(),
+            module_name: self.module_name.clone(),
+            node_types: self.node_types.clone(),
+            extensions: self.extensions.clone(),
+            registry_values: self.registry_values.clone(),
+            diagnostics: self.diagnostics.clone(),
+            translations: self.translations.clone(),
+            restriction: self.restriction.clone(),
+            servers: self.servers.clone(),
+        }
+    }
+
+    pub fn get_provider(&self) -> &str {
+        if let Some(provider) = &self.providerstringindirect {
+            provider
+        }
+        else if let Some(provider) = self.about.as_ref().and_then(|a| a.provider.as_ref()) {
+            provider
+        }
+        else {
+            ""
+        }
+    }
+
+    /// Whether this is an MMC 3.0 snap-in written in .NET, registered under
+    /// an `FX:{clsid}` key with the assembly to load.
+    pub fn is_managed(&self) -> bool {
+        self.clsid.get(..3).is_some_and(|p| p.eq_ignore_ascii_case("FX:"))
+            || self.application_base.is_some()
+            || self.registry_values.iter().any(|(name, _)| name.eq_ignore_ascii_case("FxVersion"))
+    }
+
+    /// The module implementing the snap-in: the assembly of a managed one,
+    /// otherwise the effective in-proc server of its class.
+    pub fn module(&self) -> Option<String> {
+        if self.is_managed() {
+            return match (&self.application_base, &self.module_name) {
+                (Some(base), Some(name)) => Some(format!("{}\\{}", base.trim_end_matches('\\'), name)),
+                (_, name) => name.clone(),
+            };
+        }
+
+        let clsid = normalize_clsid(&self.clsid)?;
+        self.servers.iter().find(|s| s.effective && s.clsid == clsid).map(|s| s.path.clone())
+    }
 }
 
-#[derive(Clone, Default, Debug)]
+/// What the snap-in's `ISnapinAbout` object reports. The icon and images
+/// are GDI handles, so they only exist on Windows and aren't saved with an
+/// inventory.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct MMCSnapInAbout {
     pub description: Option<String>,
     pub provider: Option<String>,
     pub version: Option<String>,
-    pub icon: Option<HICON>,
+    #[cfg(windows)]
+    #[serde(skip)]
+    pub icon: Option<windows::Win32::UI::WindowsAndMessaging::HICON>,
+    #[cfg(windows)]
+    #[serde(skip)]
     pub image: Option<MMCSnapInImage>,
 }
 
-impl Drop for MMCSnapInAbout {
-    fn drop(&mut self) {
-        trace!("Drop {:?}", self);
-        if let Some(icon) = self.icon {
-            if !icon.is_invalid() {
-                let _ = unsafe { DestroyIcon(icon) };
-            }
+impl MMCSnapInAbout {
+    pub fn has_image(&self) -> bool {
+        #[cfg(windows)]
+        return self.image.is_some();
+        #[cfg(not(windows))]
+        return false;
+    }
+
+    pub fn has_icon(&self) -> bool {
+        #[cfg(windows)]
+        return self.icon.is_some();
+        #[cfg(not(windows))]
+        return false;
+    }
+
+    /// A copy without the icon and images. Clones would share the GDI
+    /// handles, so this is the only safe way to keep a second copy.
+    pub fn text_only(&self) -> Self {
+        MMCSnapInAbout {
+            description: self.description.clone(),
+            provider: self.provider.clone(),
+            version: self.version.clone(),
+            #[cfg(windows)]
+            icon: None,
+            #[cfg(windows)]
+            image: None,
         }
     }
 }
-#[derive(Clone, Default, Debug)]
-pub struct MMCSnapInImage {
-    pub small: HBITMAP,
-    pub small_open: HBITMAP,
-    pub large: HBITMAP,
-    pub mask: COLORREF,
+
+/// Why an About object can't be read, for failures that will happen the
+/// same way until its module changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AboutFailure {
+    /// No in-proc server is registered for the class.
+    NoInprocServer,
+    /// COM doesn't know the class, `REGDB_E_CLASSNOTREG`.
+    ClassNotRegistered,
+    /// The object isn't an `ISnapinAbout`, `E_NOINTERFACE`.
+    NoInterface,
 }
 
-impl Drop for MMCSnapInImage {
-    fn drop(&mut self) {
-        unsafe {
-            let _ = DeleteObject(self.small);
-            let _ = DeleteObject(self.small_open);
-            let _ = DeleteObject(self.large);
-        }
+/// An About object failing for good, see `AboutFailure`. Anything else a
+/// resolver fails with, like a worker that timed out, may work next time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AboutError {
+    pub failure: AboutFailure,
+    /// As the system worded it, so possibly in the user's language.
+    pub message: String,
+}
+
+impl AboutError {
+    pub fn new(failure: AboutFailure, message: impl Into<String>) -> Self {
+        AboutError { failure, message: message.into() }
+    }
+}
+
+impl Display for AboutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
     }
 }
 
-trait ToWide {
-    //fn to_wide(&self) -> Vec<u16>;
-    fn to_wide_null(&self) -> Vec<u16>;
+impl Error for AboutError {}
+
+/// Parses a CLSID in any form `uuid` accepts and returns it in the braced,
+/// upper case form used by the registry and .msc files.
+pub fn normalize_clsid(s: &str) -> Option<String> {
+    let id = uuid::Uuid::parse_str(s.trim()).ok()?;
+    Some(format!("{{{}}}", id.as_hyphenated()).to_uppercase())
+}
+
+/// Turns the indirect strings and About objects a registration points at
+/// into what they say. Resolving can mean running the snap-in's own code, so
+/// it's kept apart from reading the registration. Snap-ins are read on
+/// several threads at once, so resolvers must be usable from any of them.
+pub trait Resolver: Sync {
+    /// Resolves `indirect` in the user's UI language.
+    fn load_string(&self, indirect: &IndirectString) -> Result<String, Box<dyn Error>>;
+
+    /// Resolves `indirect` in `language`, a tag like `de-DE`. Translations
+    /// are read from the module's MUI files, so nothing is loaded.
+    fn load_string_in(&self, indirect: &IndirectString, language: &str) -> Result<String, Box<dyn Error>> {
+        module::load_string_in(indirect, language)
+    }
+
+    fn about(&self, clsid: &str) -> Result<MMCSnapInAbout, Box<dyn Error>>;
 }
 
-impl ToWide for str {
-    /*
-    fn to_wide(&self) -> Vec<u16> {
-        self.encode_utf16().collect()
+impl<R: Resolver + ?Sized> Resolver for Box<R> {
+    fn load_string(&self, indirect: &IndirectString) -> Result<String, Box<dyn Error>> {
+        (**self).load_string(indirect)
+    }
+
+    fn load_string_in(&self, indirect: &IndirectString, language: &str) -> Result<String, Box<dyn Error>> {
+        (**self).load_string_in(indirect, language)
     }
-    */
 
-    fn to_wide_null(&self) -> Vec<u16> {
-        self.encode_utf16().chain(Some(0)).collect()
+    fn about(&self, clsid: &str) -> Result<MMCSnapInAbout, Box<dyn Error>> {
+        (**self).about(clsid)
     }
 }
 
-impl TryFrom<String> for MMCSnapIn {
-    type Error = Box<dyn Error>;
+/// Resolves nothing, for registrations that didn't come from this machine.
+pub struct NoResolver;
 
-    fn try_from(value: String) -> Result<Self, Self::Error> {
-        let regpath = format!("SOFTWARE\\Microsoft\\MMC\\SnapIns\\{}", value);
-        let regkey: RegKey;
-        match Hive::LocalMachine.open(
-            &regpath,
-            Security::Read
-        ) {
-            Err(e) => {
-                return Err(e.into());
+impl Resolver for NoResolver {
+    fn load_string(&self, _indirect: &IndirectString) -> Result<String, Box<dyn Error>> {
+        Err("not resolved".into())
+    }
+
+    fn load_string_in(&self, _indirect: &IndirectString, _language: &str) -> Result<String, Box<dyn Error>> {
+        Err("not resolved".into())
+    }
+
+    fn about(&self, _clsid: &str) -> Result<MMCSnapInAbout, Box<dyn Error>> {
+        Err("not resolved".into())
+    }
+}
+
+/// Reads every registered snap-in, `workers` at a time, resolving their
+/// indirect strings in each of `languages` too. Snap-ins come back in the
+/// order the source lists them, however many workers there are.
+pub fn get_snapins(source: &dyn RegistrySource, resolver: &dyn Resolver, workers: usize, languages: &[String]) -> Result<Vec<MMCSnapIn>, Box<dyn Error>> {
+    let clsids = source.subkeys(SNAPINS_KEY).ok_or(format!("{} not found", SNAPINS_KEY))?;
+
+    // Each worker takes the next unread CLSID until there are none left
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, Result<MMCSnapIn, String>)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers.clamp(1, clsids.len().max(1)))
+            .map(|_| scope.spawn(|| {
+                let mut results = Vec::new();
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(clsid) = clsids.get(i) else { break };
+                    results.push((i, MMCSnapIn::read(source, resolver, clsid, languages).map_err(|e| e.to_string())));
+                }
+                results
+            }))
+            .collect();
+        handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+
+    let mut found_snapins: Vec<MMCSnapIn> = Vec::new();
+    for (_, result) in results {
+        match result {
+            Ok(snapin) => {
+                found_snapins.push(snapin);
             }
-            Ok(key) => {
-                regkey = key;
+            Err(e) => {
+                eprintln!("{}", e);
             }
         }
+    }
+
+    Ok(found_snapins)
+}
+
+impl MMCSnapIn {
+    pub fn read(source: &dyn RegistrySource, resolver: &dyn Resolver, clsid: &str, languages: &[String]) -> Result<Self, Box<dyn Error>> {
+        let regpath = format!("{}\\{}", SNAPINS_KEY, clsid);
+        let values = source.values(&regpath).ok_or(format!("{} not found", regpath))?;
 
         let mut snapin = MMCSnapIn {
-            clsid: value,
+            clsid: clsid.to_string(),
+            restriction: MMCRestriction::read(source, clsid),
             ..Default::default()
         };
 
-        snapin.standalone = regkey
-            .keys()
-            .any(|k| {
-                k.map(|key| key.to_string().eq_ignore_ascii_case("StandAlone"))
-                    .unwrap_or(false)
-            });
+        snapin.standalone = source
+            .subkeys(&regpath)
+            .unwrap_or_default()
+            .iter()
+            .any(|k| k.eq_ignore_ascii_case("StandAlone"));
+
+        // Node types this snap-in publishes, and the snap-ins extending them
+        for node_type in source.subkeys(&format!("{}\\NodeTypes", regpath)).unwrap_or_default() {
+            snapin.extensions.extend(get_extensions(source, &node_type));
+            snapin.node_types.push(node_type);
+        }
 
-        for value in regkey.values() {
-            let value = value?;
-            match value.name().to_string_lossy().as_str() {
+        for (name, data) in values {
+            snapin.registry_values.push((name.clone(), data.to_string()));
+            let Some(data) = data.as_str() else { continue };
+            match name.as_str() {
                 "About" => {
-                    if let reg::Data::String(data) = value.data() {
-                        
-                        let id = uuid::Uuid::parse_str(data.to_string_lossy().as_str())?;
-                        let clsid = GUID::from_values(
-                            id.as_fields().0,
-                            id.as_fields().1,
-                            id.as_fields().2,
-                            id.as_fields().3.clone()
-                        );
-                        if let Ok(about) = MMCSnapInAbout::try_from(clsid) {
-                            snapin.about = Some(about);
-                        }
+                    match resolver.about(data) {
+                        Ok(about) => snapin.about = Some(about),
+                        Err(e) => snapin.diagnostics.push(format!("About {}: {}", data, e)),
                     }
                 },
                 "NameString" => {
-                    if let reg::Data::String(data) = value.data() {
-                        snapin.namestring = Some(data.to_string_lossy());
-                    }
+                    snapin.namestring = Some(data.to_string());
                 },
                 "NameStringIndirect" => {
-                    if let reg::Data::String(data) = value.data() {
-                        match nsi::IndirectString::from_str(data.to_string_lossy().as_str()) {
-                            Err(_) => {},
-                            Ok(nsi) => {
-                                let dllpath = nsi.dllpath;
-                                match load_dll_string(&dllpath, nsi.strid) {
-                                    Err(_) => {},
-                                    Ok(namestring) => {
-                                        snapin.namestringindirect = Some(namestring);
-                                    }
-                                }
-                                
-                            }
-                        }
-                    }
+                    snapin.namestringindirect = resolve_indirect(resolver, &name, data, &mut snapin.diagnostics);
                 },
                 "ProviderStringIndirect" => {
-                    if let reg::Data::String(data) = value.data() {
-                        match nsi::IndirectString::from_str(data.to_string_lossy().as_str()) {
-                            Err(_) => {},
-                            Ok(nsi) => {
-                                let dllpath = nsi.dllpath;
-                                match load_dll_string(&dllpath, nsi.strid) {
-                                    Err(_) => {},
-                                    Ok(provider) => {
-                                        snapin.providerstringindirect = Some(provider);
-                                    }
-                                }
-                                
-                            }
-                        }
-                    }
+                    snapin.providerstringindirect = resolve_indirect(resolver, &name, data, &mut snapin.diagnostics);
                 },
                 "VersionStringIndirect" => {
-                    if let reg::Data::String(data) = value.data() {
-                        match nsi::IndirectString::from_str(data.to_string_lossy().as_str()) {
-                            Err(_) => {},
-                            Ok(nsi) => {
-                                let dllpath = nsi.dllpath;
-                                match load_dll_string(&dllpath, nsi.strid) {
-                                    Err(_) => {},
-                                    Ok(version) => {
-                                        snapin.versionstringindirect = Some(version);
-                                    }
-                                }
-                                
-                            }
-                        }
-                    }
+                    snapin.versionstringindirect = resolve_indirect(resolver, &name, data, &mut snapin.diagnostics);
                 },
                 "ApplicationBase" => {
-                    if let reg::Data::String(data) = value.data() {
-                        snapin.application_base = Some(data.to_string_lossy());
-                    }
+                    snapin.application_base = Some(data.to_string());
                 },
                 "ModuleName" => {
-                    if let reg::Data::String(data) = value.data() {
-                        snapin.module_name = Some(data.to_string_lossy());
-                    }
+                    snapin.module_name = Some(data.to_string());
                 },
                 "Description" => {
-                    if let reg::Data::String(data) = value.data() {
-                        snapin.description = Some(data.to_string_lossy());
-                    }
+                    snapin.description = Some(data.to_string());
                 },
                 _ => {},
             }
+
+            // The same strings in each of the other languages asked for
+            if MMCSnapInStrings::VALUES.contains(&name.as_str()) {
+                let Ok(nsi) = IndirectString::from_str(data) else { continue };
+                for language in languages {
+                    match resolver.load_string_in(&nsi, language) {
+                        Ok(string) => {
+                            let strings = snapin.translations.entry(language.clone()).or_default();
+                            if let Some(field) = strings.field(&name) {
+                                *field = Some(string);
+                            }
+                        }
+                        Err(e) => snapin.diagnostics.push(format!("{} [{}]: {}: {}", name, language, nsi.dllpath, e)),
+                    }
+                }
+            }
+        }
+
+        // The classes COM creates for it, wherever they're registered
+        let about = snapin.registry_values.iter().find(|(name, _)| name == "About").map(|(_, data)| data.clone());
+        for class in [Some(clsid.to_string()), about.filter(|a| normalize_clsid(a) != normalize_clsid(clsid))].into_iter().flatten() {
+            snapin.servers.extend(inproc_servers(source, &class));
+        }
+        for server in snapin.servers.iter().filter(|s| s.is_user_override(&snapin.servers)) {
+            snapin.diagnostics.push(format!("{} is registered per user, overriding the machine-wide server: {}", server.clsid, server.path));
         }
 
         Ok(snapin)
     }
 }
 
-impl TryFrom<GUID> for MMCSnapInAbout {
-    type Error = Box<dyn Error>;
-
-    fn try_from(value: GUID) -> Result<Self, Self::Error> {
-        trace!("MMCSnapInAbout::TryFrom {:?}", value);
-        let mut snapin_about = MMCSnapInAbout::default();
-        unsafe {
-            match CoCreateInstance::<_, IUnknown>(&value, None, CLSCTX_INPROC_SERVER) {
-                Ok(iunk) => {
-                    let about = iunk.cast::<ISnapinAbout>();
-                    if let Ok(about) = about {
-                        trace!("\tCreated class instance");
-                        // Get description
-                        trace!("\tGetSnapinDescription()");
-                        let desc_ptr = about.GetSnapinDescription()?;
-                        if !desc_ptr.is_null() {
-                            let desc = desc_ptr.to_string()?;
-                            trace!("\tGot {} at {:#x}, freeing", desc, desc_ptr.0 as usize);
-                            CoTaskMemFree(Some(desc_ptr.0 as *const _));
-                            snapin_about.description = Some(desc);
-                        }
+fn resolve_indirect(resolver: &dyn Resolver, name: &str, data: &str, diagnostics: &mut Vec<String>) -> Option<String> {
+    match IndirectString::from_str(data) {
+        Err(e) => {
+            diagnostics.push(format!("{}: {}", name, e));
+            None
+        }
+        Ok(nsi) => match resolver.load_string(&nsi) {
+            Ok(string) => Some(string),
+            Err(e) => {
+                diagnostics.push(format!("{}: {}: {}", name, nsi.dllpath, e));
+                None
+            }
+        },
+    }
+}
 
-                        // Get provider
-                        trace!("\tGetProvider()");
-                        let prov_ptr = about.GetProvider()?;
-                        if !prov_ptr.is_null() {
-                            let prov = prov_ptr.to_string()?;
-                            trace!("\tGot {} at {:#x}, freeing", prov, prov_ptr.0 as usize);
-                            CoTaskMemFree(Some(prov_ptr.0 as *const _));
-                            snapin_about.provider = Some(prov);
-                        }
+fn get_extensions(source: &dyn RegistrySource, node_type: &str) -> Vec<MMCExtension> {
+    let mut extensions = Vec::new();
+    let regpath = format!("{}\\{}\\Extensions", NODETYPES_KEY, node_type);
+
+    // One subkey per extension kind (NameSpace, ContextMenu, PropertySheet,
+    // ...) holding a value per extending snap-in CLSID.
+    for kind in source.subkeys(&regpath).unwrap_or_default() {
+        for (clsid, data) in source.values(&format!("{}\\{}", regpath, kind)).unwrap_or_default() {
+            extensions.push(MMCExtension {
+                node_type: node_type.to_string(),
+                kind: kind.clone(),
+                clsid,
+                name: data.as_str().map(str::to_string),
+            });
+        }
+    }
 
-                        // Get version
-                        trace!("\tGetSnapinVersion()");
-                        let ver_ptr = about.GetSnapinVersion()?;
-                        if !ver_ptr.is_null() {
-                            trace!("\tGot {:?} from GetSnapinVersion()", ver_ptr);
-                            let ver = ver_ptr.to_string()?;
-                            trace!("\tGot {} at {:#x}, freeing", ver, ver_ptr.0 as usize);
-                            CoTaskMemFree(Some(ver_ptr.0 as *const _));
-                            snapin_about.version = Some(ver);
-                        }
+    extensions
+}
 
-                        // Get icon
-                        let icon_ptr = about.GetSnapinImage()?;
-                        if !icon_ptr.is_invalid() {
-                            let my_icon = CopyIcon(icon_ptr)?;
-                            snapin_about.icon = Some(my_icon);
-                        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::RegFile;
+
+    const TEST_REG: &str = r#"Windows Registry Editor Version 5.00
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}]
+"NameStringIndirect"="@%SystemRoot%\\system32\\mycomput.dll,-106"
+"About"="{58221C67-EA27-11CF-ADCF-00AA00A80033}"
+"ModuleName"="mycomput.dll"
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}\StandAlone]
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}\NodeTypes\{476E6449-AAFF-11D0-B944-00C04FD8D5B0}]
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\NodeTypes\{476E6449-AAFF-11D0-B944-00C04FD8D5B0}\Extensions\NameSpace]
+"{394C052E-B830-11D0-9A86-00C04FD8DBF7}"="Event Viewer Extension"
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{394C052E-B830-11D0-9A86-00C04FD8DBF7}]
+"NameString"="Event Viewer"
+"#;
+
+    #[test]
+    fn test_get_snapins_from_reg_file() {
+        let file = RegFile::parse(TEST_REG).unwrap();
+        let snapins = get_snapins(&file, &NoResolver, 1, &[]).unwrap();
+        assert_eq!(snapins.len(), 2);
+
+        let compmgmt = &snapins[1];
+        assert_eq!(compmgmt.clsid, "{58221C67-EA27-11CF-ADCF-00AA00A80033}");
+        assert!(compmgmt.standalone);
+        assert_eq!(compmgmt.module_name.as_deref(), Some("mycomput.dll"));
+        assert_eq!(compmgmt.node_types, vec!["{476E6449-AAFF-11D0-B944-00C04FD8D5B0}"]);
+        assert_eq!(compmgmt.extensions, vec![MMCExtension {
+            node_type: "{476E6449-AAFF-11D0-B944-00C04FD8D5B0}".into(),
+            kind: "NameSpace".into(),
+            clsid: "{394C052E-B830-11D0-9A86-00C04FD8DBF7}".into(),
+            name: Some("Event Viewer Extension".into()),
+        }]);
+        // Nothing resolved, but the failures are recorded
+        assert!(compmgmt.namestringindirect.is_none());
+        assert_eq!(compmgmt.diagnostics.len(), 2);
+        assert_eq!(compmgmt.registry_values.len(), 3);
+
+        assert_eq!(snapins[0].get_name(), "Event Viewer");
+        assert!(!snapins[0].standalone);
+    }
 
-                        // Get images
-
-                        let mut small = Gdi::HBITMAP(0);
-                        let mut small_open = Gdi::HBITMAP(0);
-                        let mut large = Gdi::HBITMAP(0);
-                        let mut cmask = windows::Win32::Foundation::COLORREF(0);
-                        match about.GetStaticFolderImage(
-                            &mut small, 
-                            &mut small_open, 
-                            &mut large, 
-                            &mut cmask
-                        ) {
-                            Ok(_) => {
-                                let image = MMCSnapInImage {
-                                    small: copy_bitmap(small),
-                                    small_open: copy_bitmap(small_open),
-                                    large: copy_bitmap(large),
-                                    mask: cmask.clone(),
-                                };
-
-                                snapin_about.image = Some(image);
-                            }
-                            Err(_) => {
+    #[test]
+    fn test_get_snapins_order_independent_of_workers() {
+        let mut reg = String::from("Windows Registry Editor Version 5.00\n");
+        for i in 0..50 {
+            reg += &format!("\n[HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\MMC\\SnapIns\\{{00000000-0000-0000-0000-{:012}}}]\n", i);
+            reg += &format!("\"NameString\"=\"Snap-in {}\"\n", i);
+        }
+        let file = RegFile::parse(&reg).unwrap();
 
-                            }
-                        }
-                        // Ref counting?
-                        //drop(about_ref);
+        let names = |workers| -> Vec<String> {
+            get_snapins(&file, &NoResolver, workers, &[]).unwrap().iter().map(|s| s.get_name().to_string()).collect()
+        };
+        let sequential = names(1);
+        assert_eq!(sequential.len(), 50);
+        assert_eq!(names(8), sequential);
+        assert_eq!(names(0), sequential);
+    }
 
-                        // Return filled struct
-                        Ok(snapin_about)
-                    }
-                    else {
-                        Err("ISnapInAbout not supported".into())
-                    }
-                }
-                Err(e) => Err(e.into())
+    // Knows German strings only
+    struct GermanResolver;
+
+    impl Resolver for GermanResolver {
+        fn load_string(&self, _: &IndirectString) -> Result<String, Box<dyn Error>> {
+            Ok("Computer Management".into())
+        }
+
+        fn load_string_in(&self, _: &IndirectString, language: &str) -> Result<String, Box<dyn Error>> {
+            match language {
+                "de-DE" => Ok("Computerverwaltung".into()),
+                _ => Err("no translation".into()),
             }
         }
-        
-    }
-}
 
-fn load_dll_string(dll_path: &str, str_id: i32) -> Result<String, Box<dyn Error>> {
-    unsafe {
-        let h_module = LoadLibraryW(PCWSTR(dll_path.to_wide_null().as_ptr()))?;
+        fn about(&self, _: &str) -> Result<MMCSnapInAbout, Box<dyn Error>> {
+            Err("no About object".into())
+        }
+    }
 
-        let mut buffer: [u16; 260] = [0; 260];
-        let length = LoadStringW(h_module, str_id as u32, PWSTR(buffer.as_mut_ptr()), buffer.len() as i32);
+    #[test]
+    fn test_translations() {
+        let file = RegFile::parse(TEST_REG).unwrap();
+        let languages = ["de-DE".to_string(), "fr-FR".to_string()];
+        let snapins = get_snapins(&file, &GermanResolver, 1, &languages).unwrap();
 
-        if length == 0 {
-            return Err("Failed to load string resource".into());
-        }
+        let compmgmt = &snapins[1];
+        assert_eq!(compmgmt.translations.len(), 1);
+        assert_eq!(compmgmt.translations["de-DE"].name.as_deref(), Some("Computerverwaltung"));
+        assert_eq!(compmgmt.missing_translations(&languages), vec!["fr-FR NameStringIndirect"]);
 
-        let string = String::from_utf16_lossy(&buffer[..length as usize]);
-        Ok(string)
+        // Nothing to translate without indirect strings
+        assert!(snapins[0].translations.is_empty());
+        assert!(snapins[0].missing_translations(&languages).is_empty());
     }
 }
-
-fn copy_bitmap(src: HBITMAP) -> HBITMAP {
-    let src_h = windows::Win32::Foundation::HANDLE(src.0);
-    let dst_h = unsafe { CopyImage(src_h, IMAGE_BITMAP, 48, 48, IMAGE_FLAGS(0)).unwrap() };
-    HBITMAP(dst_h.0)
-}
\ No newline at end of file
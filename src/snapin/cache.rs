@@ -0,0 +1,437 @@
+use std::{collections::HashMap, error::Error, fmt::{self, Display}, fs, hash::Hash, io::Read, path::{Path, PathBuf}};
+use std::sync::{atomic::{AtomicUsize, Ordering}, Mutex};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::nsi::IndirectString;
+use crate::source::RegistrySource;
+use super::module::{inproc_server, module_path, ui_language, ModuleStamp};
+use super::{AboutError, MMCSnapInAbout, Resolver};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+struct StringKey {
+    module: ModuleStamp,
+    id: i32,
+    // Empty if the user's UI language isn't known
+    language: String,
+    // Asked for in the UI language, which falls back to the module's own
+    // strings where `load_string_in` doesn't
+    ui: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+struct AboutKey {
+    clsid: String,
+    module_hash: String,
+}
+
+// About failures that last are cached too, so a broken snap-in isn't
+// retried every run. Strings that couldn't be read aren't, since a file
+// being replaced fails the same way as one that's broken.
+#[derive(Default)]
+struct Entries {
+    strings: HashMap<StringKey, String>,
+    abouts: HashMap<AboutKey, Result<MMCSnapInAbout, AboutError>>,
+    hashes: HashMap<ModuleStamp, String>,
+}
+
+// JSON objects can only have string keys, so the maps are saved as lists
+#[derive(Default, Serialize, Deserialize)]
+struct CacheFile {
+    strings: Vec<(StringKey, String)>,
+    abouts: Vec<(AboutKey, Result<MMCSnapInAbout, AboutError>)>,
+    hashes: Vec<(ModuleStamp, String)>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// Lookups whose module couldn't be found, so couldn't be cached
+    pub uncached: usize,
+}
+
+impl Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} hits, {} misses, {} not cacheable", self.hits, self.misses, self.uncached)
+    }
+}
+
+/// Remembers what another resolver resolved, keyed by the module it came
+/// from, so snap-ins whose modules haven't changed aren't resolved again on
+/// the next run. Strings are keyed by the DLL's path, size, modification time
+/// and language, About objects by CLSID and a hash of their in-proc server.
+///
+/// Only the text of an About object is cached; its icon and images are GDI
+/// handles. Use `strings_only` when they're needed.
+pub struct CachingResolver<'a, R> {
+    inner: R,
+    source: &'a dyn RegistrySource,
+    path: PathBuf,
+    // What `load_string` resolves in, so changing it doesn't return strings
+    // in the old one
+    ui_language: &'static str,
+    cache_about: bool,
+    // What was saved last time, and what's been used this run. Only the
+    // latter is saved, which drops entries for modules that changed.
+    saved: Entries,
+    used: Mutex<Entries>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    uncached: AtomicUsize,
+}
+
+/// Where a cache named `name` lives by default: under `%LOCALAPPDATA%` on
+/// Windows, `$XDG_CACHE_HOME` or `~/.cache` elsewhere.
+pub fn default_path(name: &str) -> Option<PathBuf> {
+    let dir = std::env::var_os("LOCALAPPDATA")
+        .or_else(|| std::env::var_os("XDG_CACHE_HOME"))
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(dir.join("enum-snapins").join(format!("{}.json", name)))
+}
+
+impl<'a, R: Resolver> CachingResolver<'a, R> {
+    /// Wraps `inner` with the cache saved at `path`, if any. `source` is
+    /// where About CLSIDs are looked up.
+    pub fn load(inner: R, source: &'a dyn RegistrySource, path: &Path) -> Self {
+        let saved = match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str::<CacheFile>(&json).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable cache {}: {}", path.display(), e);
+                CacheFile::default()
+            }),
+            Err(_) => CacheFile::default(),
+        };
+
+        CachingResolver {
+            inner,
+            source,
+            path: path.to_path_buf(),
+            ui_language: ui_language().unwrap_or_default(),
+            cache_about: true,
+            saved: Entries {
+                strings: saved.strings.into_iter().collect(),
+                abouts: saved.abouts.into_iter().collect(),
+                hashes: saved.hashes.into_iter().collect(),
+            },
+            used: Mutex::new(Entries::default()),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            uncached: AtomicUsize::new(0),
+        }
+    }
+
+    /// Always asks the inner resolver for About objects, so they keep their
+    /// icon and images.
+    pub fn strings_only(mut self) -> Self {
+        self.cache_about = false;
+        self
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            uncached: self.uncached.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Writes everything used this run back to the cache file.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let used = self.used.lock().unwrap();
+        let mut file = CacheFile {
+            strings: used.strings.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            abouts: used.abouts.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            hashes: used.hashes.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        };
+
+        // Keep the file stable between runs that resolved the same things
+        file.strings.sort_by(|a, b| a.0.cmp(&b.0));
+        file.abouts.sort_by(|a, b| a.0.cmp(&b.0));
+        file.hashes.sort_by(|a, b| a.0.cmp(&b.0));
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string(&file)?)?;
+        Ok(())
+    }
+
+    fn cached_string(
+        &self,
+        indirect: &IndirectString,
+        language: &str,
+        ui: bool,
+        resolve: impl FnOnce() -> Result<String, Box<dyn Error>>,
+    ) -> Result<String, Box<dyn Error>> {
+        let Ok(module) = ModuleStamp::of(&module_path(&indirect.dllpath)) else {
+            self.uncached.fetch_add(1, Ordering::Relaxed);
+            return resolve();
+        };
+
+        let key = StringKey { module, id: indirect.strid, language: language.to_string(), ui };
+        if let Some(string) = self.cached(&key, |e| &e.strings, |e| &mut e.strings) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(string);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let string = resolve()?;
+        self.used.lock().unwrap().strings.insert(key, string.clone());
+        Ok(string)
+    }
+
+    // Looks `key` up in this run's entries, then the saved ones
+    fn cached<K: Eq + Hash + Clone, V: Clone>(
+        &self,
+        key: &K,
+        map: impl Fn(&Entries) -> &HashMap<K, V>,
+        map_mut: impl Fn(&mut Entries) -> &mut HashMap<K, V>,
+    ) -> Option<V> {
+        let mut used = self.used.lock().unwrap();
+        if let Some(value) = map(&used).get(key) {
+            return Some(value.clone());
+        }
+
+        let value = map(&self.saved).get(key)?.clone();
+        map_mut(&mut used).insert(key.clone(), value.clone());
+        Some(value)
+    }
+
+    fn module_hash(&self, path: &Path) -> Result<String, Box<dyn Error>> {
+        let stamp = ModuleStamp::of(path)?;
+        if let Some(hash) = self.cached(&stamp, |e| &e.hashes, |e| &mut e.hashes) {
+            return Ok(hash);
+        }
+
+        let mut file = fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+
+        let hash: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        self.used.lock().unwrap().hashes.insert(stamp, hash.clone());
+        Ok(hash)
+    }
+}
+
+impl<R: Resolver> Resolver for CachingResolver<'_, R> {
+    fn load_string(&self, indirect: &IndirectString) -> Result<String, Box<dyn Error>> {
+        self.cached_string(indirect, self.ui_language, true, || self.inner.load_string(indirect))
+    }
+
+    fn load_string_in(&self, indirect: &IndirectString, language: &str) -> Result<String, Box<dyn Error>> {
+        self.cached_string(indirect, language, false, || self.inner.load_string_in(indirect, language))
+    }
+
+    fn about(&self, clsid: &str) -> Result<MMCSnapInAbout, Box<dyn Error>> {
+        let module_hash = inproc_server(self.source, clsid).and_then(|server| self.module_hash(&module_path(&server)));
+        let (true, Ok(module_hash)) = (self.cache_about, module_hash) else {
+            self.uncached.fetch_add(1, Ordering::Relaxed);
+            return self.inner.about(clsid);
+        };
+
+        let key = AboutKey {
+            clsid: super::normalize_clsid(clsid).unwrap_or(clsid.to_string()),
+            module_hash,
+        };
+        if let Some(result) = self.cached(&key, |e| &e.abouts, |e| &mut e.abouts) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return result.map_err(|e| e.into());
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let result = self.inner.about(clsid);
+        let lasting = match &result {
+            Ok(about) => Some(Ok(about.text_only())),
+            Err(e) => e.downcast_ref::<AboutError>().cloned().map(Err),
+        };
+        if let Some(lasting) = lasting {
+            self.used.lock().unwrap().abouts.insert(key, lasting);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapin::AboutFailure;
+    use crate::source::RegFile;
+
+    // Counts how often it's asked, and answers with the module's contents
+    struct CountingResolver(AtomicUsize);
+
+    impl Resolver for CountingResolver {
+        fn load_string(&self, indirect: &IndirectString) -> Result<String, Box<dyn Error>> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Ok(format!("{} {}", fs::read_to_string(&indirect.dllpath)?, indirect.strid))
+        }
+
+        fn about(&self, clsid: &str) -> Result<MMCSnapInAbout, Box<dyn Error>> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Err(AboutError::new(AboutFailure::NoInterface, format!("{}: ISnapInAbout not supported", clsid)).into())
+        }
+    }
+
+    // Fails the way an isolated worker does when it runs out of time
+    struct TimingOutResolver(AtomicUsize);
+
+    impl Resolver for TimingOutResolver {
+        fn load_string(&self, _indirect: &IndirectString) -> Result<String, Box<dyn Error>> {
+            Err("not resolved".into())
+        }
+
+        fn about(&self, _clsid: &str) -> Result<MMCSnapInAbout, Box<dyn Error>> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Err("About worker timed out after 10s".into())
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("enum-snapins-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_cache_strings() {
+        let dir = temp_dir("cache-strings");
+        let module = dir.join("snapin.dll");
+        fs::write(&module, "v1").unwrap();
+        let indirect = IndirectString { dllpath: module.to_string_lossy().into(), strid: 100 };
+        let cache = dir.join("cache.json");
+        let file = RegFile::default();
+
+        let resolver = CachingResolver::load(CountingResolver(AtomicUsize::new(0)), &file, &cache);
+        assert_eq!(resolver.load_string(&indirect).unwrap(), "v1 100");
+        assert_eq!(resolver.load_string(&indirect).unwrap(), "v1 100");
+        assert_eq!(resolver.stats(), CacheStats { hits: 1, misses: 1, uncached: 0 });
+        resolver.save().unwrap();
+
+        // Saved for the next run
+        let resolver = CachingResolver::load(CountingResolver(AtomicUsize::new(0)), &file, &cache);
+        assert_eq!(resolver.load_string(&indirect).unwrap(), "v1 100");
+        assert_eq!(resolver.inner.0.load(Ordering::Relaxed), 0);
+
+        // Changing the module invalidates it
+        fs::write(&module, "v2 is longer").unwrap();
+        assert_eq!(resolver.load_string(&indirect).unwrap(), "v2 is longer 100");
+        assert_eq!(resolver.stats(), CacheStats { hits: 1, misses: 1, uncached: 0 });
+
+        let missing = IndirectString { dllpath: dir.join("missing.dll").to_string_lossy().into(), strid: 1 };
+        assert!(resolver.load_string(&missing).is_err());
+        assert_eq!(resolver.stats().uncached, 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_string_failures_not_cached() {
+        let dir = temp_dir("cache-strings-failures");
+        let module = dir.join("snapin.dll");
+        fs::write(&module, "half written").unwrap();
+        let indirect = IndirectString { dllpath: module.to_string_lossy().into(), strid: 100 };
+        let file = RegFile::default();
+
+        let resolver = CachingResolver::load(TimingOutResolver(AtomicUsize::new(0)), &file, &dir.join("cache.json"));
+        assert!(resolver.load_string(&indirect).is_err());
+        assert!(resolver.load_string(&indirect).is_err());
+        assert_eq!(resolver.stats(), CacheStats { hits: 0, misses: 2, uncached: 0 });
+        resolver.save().unwrap();
+        assert!(!fs::read_to_string(dir.join("cache.json")).unwrap().contains("not resolved"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_cache_strings_by_ui_language() {
+        let dir = temp_dir("cache-strings-language");
+        let module = dir.join("snapin.dll");
+        fs::write(&module, "v1").unwrap();
+        let indirect = IndirectString { dllpath: module.to_string_lossy().into(), strid: 100 };
+        let cache = dir.join("cache.json");
+        let file = RegFile::default();
+
+        let mut resolver = CachingResolver::load(CountingResolver(AtomicUsize::new(0)), &file, &cache);
+        resolver.ui_language = "en-US";
+        resolver.load_string(&indirect).unwrap();
+        resolver.save().unwrap();
+
+        // The user switched to German since
+        let mut resolver = CachingResolver::load(CountingResolver(AtomicUsize::new(0)), &file, &cache);
+        resolver.ui_language = "de-DE";
+        resolver.load_string(&indirect).unwrap();
+        assert_eq!(resolver.inner.0.load(Ordering::Relaxed), 1);
+        resolver.load_string(&indirect).unwrap();
+        assert_eq!(resolver.stats(), CacheStats { hits: 1, misses: 1, uncached: 0 });
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_cache_about_failures() {
+        let dir = temp_dir("cache-about");
+        let module = dir.join("about.dll");
+        fs::write(&module, "about").unwrap();
+        let file = RegFile::parse(&format!(
+            "[HKEY_LOCAL_MACHINE\\SOFTWARE\\Classes\\CLSID\\{{58221C67-EA27-11CF-ADCF-00AA00A80033}}\\InprocServer32]\n@=\"{}\"\n",
+            module.to_string_lossy().replace('\\', "\\\\")
+        )).unwrap();
+
+        let resolver = CachingResolver::load(CountingResolver(AtomicUsize::new(0)), &file, &dir.join("cache.json"));
+        let clsid = "{58221c67-ea27-11cf-adcf-00aa00a80033}";
+        assert!(resolver.about(clsid).is_err());
+        let error = resolver.about(clsid).unwrap_err();
+        assert_eq!(error.downcast_ref::<AboutError>().map(|e| e.failure), Some(AboutFailure::NoInterface));
+        assert_eq!(resolver.inner.0.load(Ordering::Relaxed), 1);
+        resolver.save().unwrap();
+
+        // Still known by its kind on the next run, whatever its wording
+        let resolver = CachingResolver::load(CountingResolver(AtomicUsize::new(0)), &file, &dir.join("cache.json"));
+        let error = resolver.about(clsid).unwrap_err();
+        assert_eq!(error.downcast_ref::<AboutError>().map(|e| e.failure), Some(AboutFailure::NoInterface));
+        assert_eq!(resolver.inner.0.load(Ordering::Relaxed), 0);
+
+        let resolver = resolver.strings_only();
+        assert!(resolver.about(clsid).is_err());
+        assert_eq!(resolver.inner.0.load(Ordering::Relaxed), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_transient_about_failures_not_cached() {
+        let dir = temp_dir("cache-about-transient");
+        let module = dir.join("about.dll");
+        fs::write(&module, "about").unwrap();
+        let file = RegFile::parse(&format!(
+            "[HKEY_LOCAL_MACHINE\\SOFTWARE\\Classes\\CLSID\\{{58221C67-EA27-11CF-ADCF-00AA00A80033}}\\InprocServer32]\n@=\"{}\"\n",
+            module.to_string_lossy().replace('\\', "\\\\")
+        )).unwrap();
+        let cache = dir.join("cache.json");
+        let clsid = "{58221C67-EA27-11CF-ADCF-00AA00A80033}";
+
+        let resolver = CachingResolver::load(TimingOutResolver(AtomicUsize::new(0)), &file, &cache);
+        assert!(resolver.about(clsid).is_err());
+        assert!(resolver.about(clsid).is_err());
+        assert_eq!(resolver.inner.0.load(Ordering::Relaxed), 2);
+        resolver.save().unwrap();
+
+        // Nor remembered for the next run
+        let resolver = CachingResolver::load(TimingOutResolver(AtomicUsize::new(0)), &file, &cache);
+        assert!(resolver.about(clsid).is_err());
+        assert_eq!(resolver.inner.0.load(Ordering::Relaxed), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,188 @@
+use std::{error::Error, io::Read, path::PathBuf, process::{Command, Stdio}, thread, time::{Duration, Instant}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::nsi::IndirectString;
+use super::{AboutError, MMCSnapInAbout, Resolver};
+
+// Starts the line the worker writes its answer on, so it can be told apart
+// from anything else the snap-in or the logger writes
+const ANSWER_MARKER: &str = "enum-snapins-about: ";
+
+// What the worker answers: the About object, or why it can't be read
+#[derive(Serialize, Deserialize)]
+enum Answer {
+    About(MMCSnapInAbout),
+    /// Failures that last, so they can be cached like any other resolver's.
+    Failed(AboutError),
+}
+
+/// The line an about worker prints for `about`, for `IsolatedResolver` to
+/// find among the rest of its output. Failures that last are part of the
+/// answer; any other error is returned, for the worker to fail with.
+pub fn worker_answer(about: Result<MMCSnapInAbout, Box<dyn Error>>) -> Result<String, Box<dyn Error>> {
+    let answer = match about {
+        Ok(about) => Answer::About(about),
+        Err(e) => Answer::Failed(e.downcast_ref::<AboutError>().cloned().ok_or(e)?),
+    };
+    Ok(format!("{}{}", ANSWER_MARKER, serde_json::to_string(&answer)?))
+}
+
+/// Probes About objects in a child process, one per snap-in, so a snap-in
+/// that crashes or hangs only costs its own About details. The child prints
+/// the `MMCSnapInAbout` or an `AboutError` as a `worker_answer` line; icons and
+/// images are GDI handles and don't survive the trip, so snap-ins probed
+/// this way have none. Strings are still resolved by `strings`.
+pub struct IsolatedResolver<R> {
+    strings: R,
+    program: PathBuf,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl<R: Resolver> IsolatedResolver<R> {
+    /// Runs `enum-snapins about-worker <clsid>` for each About object.
+    pub fn new(strings: R, timeout: Duration) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::with_command(strings, std::env::current_exe()?, vec!["about-worker".to_string()], timeout))
+    }
+
+    /// Runs `program args... <clsid>` for each About object.
+    pub fn with_command(strings: R, program: PathBuf, args: Vec<String>, timeout: Duration) -> Self {
+        IsolatedResolver { strings, program, args, timeout }
+    }
+}
+
+impl<R: Resolver> Resolver for IsolatedResolver<R> {
+    fn load_string(&self, indirect: &IndirectString) -> Result<String, Box<dyn Error>> {
+        self.strings.load_string(indirect)
+    }
+
+    fn load_string_in(&self, indirect: &IndirectString, language: &str) -> Result<String, Box<dyn Error>> {
+        self.strings.load_string_in(indirect, language)
+    }
+
+    fn about(&self, clsid: &str) -> Result<MMCSnapInAbout, Box<dyn Error>> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .arg(clsid)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Drain the pipes while waiting so a chatty child can't block on them
+        let mut stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        let stdout = thread::spawn(move || {
+            let mut output = String::new();
+            let _ = stdout.read_to_string(&mut output);
+            output
+        });
+        let stderr = thread::spawn(move || {
+            let mut output = String::new();
+            let _ = stderr.read_to_string(&mut output);
+            output
+        });
+
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() >= self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("About worker timed out after {:?}", self.timeout).into());
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+
+        if !status.success() {
+            let reason = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("no output");
+            return Err(format!("About worker failed ({}): {}", status, reason.trim()).into());
+        }
+
+        let json = stdout
+            .lines()
+            .rev()
+            .find_map(|l| l.trim_end().strip_prefix(ANSWER_MARKER))
+            .ok_or("About worker returned no answer")?;
+        match serde_json::from_str(json).map_err(|e| format!("About worker returned invalid data: {}", e))? {
+            Answer::About(about) => Ok(about),
+            Answer::Failed(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::snapin::{AboutFailure, NoResolver};
+
+    fn shell(script: &str, timeout: Duration) -> IsolatedResolver<NoResolver> {
+        let args = vec!["-c".to_string(), script.to_string(), "sh".to_string()];
+        IsolatedResolver::with_command(NoResolver, PathBuf::from("sh"), args, timeout)
+    }
+
+    #[test]
+    fn test_about_from_worker() {
+        let about = MMCSnapInAbout {
+            description: Some("Manages computers".into()),
+            provider: Some("Contoso".into()),
+            ..Default::default()
+        };
+        let answer = worker_answer(Ok(about)).unwrap();
+
+        // Log lines on both sides, like the worker's logger and the About
+        // object's Drop write
+        let script = format!("echo 'TRACE [enum_snapins] probing $1'; echo '{}'; echo 'TRACE [enum_snapins] Drop'", answer);
+        let resolver = shell(&script, Duration::from_secs(10));
+
+        let about = resolver.about("{58221C67-EA27-11CF-ADCF-00AA00A80033}").unwrap();
+        assert_eq!(about.description.as_deref(), Some("Manages computers"));
+        assert_eq!(about.provider.as_deref(), Some("Contoso"));
+        assert_eq!(about.version, None);
+    }
+
+    #[test]
+    fn test_about_worker_failure() {
+        let failure = AboutError::new(AboutFailure::ClassNotRegistered, "Klasse nicht registriert");
+        let answer = worker_answer(Err(failure.clone().into())).unwrap();
+        let resolver = shell(&format!("echo '{}'", answer), Duration::from_secs(10));
+
+        let error = resolver.about("{58221C67-EA27-11CF-ADCF-00AA00A80033}").unwrap_err();
+        assert_eq!(error.downcast_ref::<AboutError>(), Some(&failure));
+
+        // Anything else fails the worker instead
+        assert!(worker_answer(Err("access violation".into())).is_err());
+    }
+
+    #[test]
+    fn test_about_worker_no_answer() {
+        let resolver = shell(r#"echo '{"description":null,"provider":null,"version":null}'"#, Duration::from_secs(10));
+
+        let error = resolver.about("{58221C67-EA27-11CF-ADCF-00AA00A80033}").unwrap_err().to_string();
+        assert!(error.contains("no answer"), "{}", error);
+    }
+
+    #[test]
+    fn test_about_worker_crash() {
+        let resolver = shell("echo 'Error: access violation' >&2; exit 3", Duration::from_secs(10));
+
+        let error = resolver.about("{58221C67-EA27-11CF-ADCF-00AA00A80033}").unwrap_err().to_string();
+        assert!(error.contains("access violation"), "{}", error);
+    }
+
+    #[test]
+    fn test_about_worker_timeout() {
+        let resolver = shell("sleep 10", Duration::from_millis(100));
+
+        let started = Instant::now();
+        let error = resolver.about("{58221C67-EA27-11CF-ADCF-00AA00A80033}").unwrap_err().to_string();
+        assert!(error.contains("timed out"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
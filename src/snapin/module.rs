@@ -0,0 +1,208 @@
+use std::{error::Error, fs, path::{Path, PathBuf}, time::UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::nsi::IndirectString;
+use crate::pe::{self, PeResources};
+use crate::source::RegistrySource;
+use super::{AboutError, AboutFailure};
+
+/// Where classes are registered, in the order COM looks: per-user first, so
+/// a registration there shadows the machine's. `HKEY_CLASSES_ROOT` is the
+/// two merged, and only looked at when neither has the class, as in `.reg`
+/// files exported from it.
+pub const CLASSES_ROOTS: [&str; 3] = [
+    r"HKEY_CURRENT_USER\Software\Classes\CLSID",
+    r"HKEY_LOCAL_MACHINE\SOFTWARE\Classes\CLSID",
+    r"HKEY_CLASSES_ROOT\CLSID",
+];
+
+/// A class's in-proc server as registered in one of `CLASSES_ROOTS`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MMCServer {
+    pub clsid: String,
+    /// The root it's registered under.
+    pub root: String,
+    pub path: String,
+    /// Whether this is the registration COM uses.
+    pub effective: bool,
+}
+
+impl MMCServer {
+    /// Whether this is a per-user registration shadowing one for the whole
+    /// machine, which anyone who can write to the user's registry can add.
+    pub fn is_user_override(&self, servers: &[MMCServer]) -> bool {
+        self.root == CLASSES_ROOTS[0] && servers.iter().any(|s| s.clsid == self.clsid && s.root != CLASSES_ROOTS[0])
+    }
+}
+
+/// Every registration of `clsid`'s in-proc server, the effective one first.
+pub fn inproc_servers(source: &dyn RegistrySource, clsid: &str) -> Vec<MMCServer> {
+    let Some(clsid) = super::normalize_clsid(clsid) else { return Vec::new() };
+    let mut servers: Vec<MMCServer> = Vec::new();
+
+    for root in CLASSES_ROOTS {
+        if root == CLASSES_ROOTS[2] && !servers.is_empty() {
+            break;
+        }
+        let values = source.values(&format!("{}\\{}\\InprocServer32", root, clsid)).unwrap_or_default();
+        if let Some(path) = values.iter().find(|(name, _)| name.is_empty()).and_then(|(_, data)| data.as_str()) {
+            servers.push(MMCServer { clsid: clsid.clone(), root: root.to_string(), path: path.to_string(), effective: servers.is_empty() });
+        }
+    }
+
+    servers
+}
+
+/// The DLL COM loads as the in-proc server of `clsid`, as written in the
+/// registry.
+pub fn inproc_server(source: &dyn RegistrySource, clsid: &str) -> Result<String, Box<dyn Error>> {
+    let normalized = super::normalize_clsid(clsid).ok_or(format!("invalid CLSID {}", clsid))?;
+    inproc_servers(source, &normalized)
+        .into_iter()
+        .next()
+        .map(|server| server.path)
+        .ok_or_else(|| AboutError::new(AboutFailure::NoInprocServer, format!("no InprocServer32 registered for {}", normalized)).into())
+}
+
+/// Expands `%VARIABLE%`s in a registered module path, and looks for bare
+/// file names in System32 the way `LoadLibrary` would find them.
+pub fn module_path(path: &str) -> PathBuf {
+    let path = expand_environment(path.trim().trim_matches('"'));
+    if path.contains(['\\', '/']) {
+        return PathBuf::from(path);
+    }
+
+    let system_root = std::env::var("SystemRoot").unwrap_or_else(|_| r"C:\Windows".to_string());
+    PathBuf::from(format!(r"{}\System32\{}", system_root, path))
+}
+
+fn expand_environment(s: &str) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find('%') {
+        let Some(len) = rest[start + 1..].find('%') else { break };
+        let name = &rest[start + 1..start + 1 + len];
+        out.push_str(&rest[..start]);
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => out.push_str(&value),
+            _ => out.push_str(&rest[start..start + len + 2]),
+        }
+        rest = &rest[start + len + 2..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Resolves `indirect` in `language` (a tag like `de-DE`) from the files on
+/// disk, the way the MUI loader would: from the module's satellite
+/// `<language>\<module>.mui` if there is one, otherwise from the module's
+/// own resources in that language.
+pub fn load_string_in(indirect: &IndirectString, language: &str) -> Result<String, Box<dyn Error>> {
+    let path = module_path(&indirect.dllpath);
+    let id = u32::try_from(indirect.strid)?;
+
+    let file_name = path.file_name().ok_or("no module file name")?.to_string_lossy();
+    let mui = path.with_file_name(language).join(format!("{}.mui", file_name));
+    if mui.is_file() {
+        return PeResources::read(&mui)?
+            .string(id)
+            .ok_or_else(|| format!("string {} not found in {}", id, mui.display()).into());
+    }
+
+    let language_id = pe::language_id(language).ok_or(format!("unknown language {}", language))?;
+    PeResources::read(&path)?
+        .string_in(id, language_id)
+        .ok_or_else(|| format!("no {} translation of string {}", language, id).into())
+}
+
+/// Identifies one version of a module file: if any of these change, so
+/// might anything read from it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ModuleStamp {
+    pub path: String,
+    pub size: u64,
+    /// Last modified, in milliseconds since the Unix epoch
+    pub modified: u64,
+}
+
+impl ModuleStamp {
+    pub fn of(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_millis() as u64;
+
+        Ok(ModuleStamp {
+            path: path.to_string_lossy().to_lowercase(),
+            size: metadata.len(),
+            modified,
+        })
+    }
+}
+
+/// The tag of the user's UI language, like `de-DE`, if it's one Windows
+/// ships in.
+#[cfg(windows)]
+pub fn ui_language() -> Option<&'static str> {
+    use windows::Win32::Globalization::GetUserDefaultUILanguage;
+    pe::language_tag(unsafe { GetUserDefaultUILanguage() })
+}
+
+#[cfg(not(windows))]
+pub fn ui_language() -> Option<&'static str> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::RegFile;
+
+    #[test]
+    fn test_expand_environment() {
+        std::env::set_var("ENUM_SNAPINS_TEST_ROOT", r"C:\Windows");
+
+        assert_eq!(expand_environment(r"%ENUM_SNAPINS_TEST_ROOT%\system32\mmc.exe"), r"C:\Windows\system32\mmc.exe");
+        assert_eq!(expand_environment(r"%ENUM_SNAPINS_UNSET%\a.dll"), r"%ENUM_SNAPINS_UNSET%\a.dll");
+        assert_eq!(expand_environment("100%"), "100%");
+    }
+
+    #[test]
+    fn test_inproc_server() {
+        let file = RegFile::parse(r#"Windows Registry Editor Version 5.00
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Classes\CLSID\{58221C67-EA27-11CF-ADCF-00AA00A80033}\InprocServer32]
+@="%SystemRoot%\\System32\\mycomput.dll"
+"ThreadingModel"="Apartment"
+"#).unwrap();
+
+        assert_eq!(
+            inproc_server(&file, "{58221c67-ea27-11cf-adcf-00aa00a80033}").unwrap(),
+            r"%SystemRoot%\System32\mycomput.dll"
+        );
+        assert!(inproc_server(&file, "{00000000-0000-0000-0000-000000000000}").is_err());
+    }
+
+    #[test]
+    fn test_user_override() {
+        let file = RegFile::parse(r#"Windows Registry Editor Version 5.00
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Classes\CLSID\{58221C67-EA27-11CF-ADCF-00AA00A80033}\InprocServer32]
+@="%SystemRoot%\\System32\\mycomput.dll"
+
+[HKEY_CLASSES_ROOT\CLSID\{58221C67-EA27-11CF-ADCF-00AA00A80033}\InprocServer32]
+@="%SystemRoot%\\System32\\mycomput.dll"
+
+[HKEY_CURRENT_USER\Software\Classes\CLSID\{58221C67-EA27-11CF-ADCF-00AA00A80033}\InprocServer32]
+@="C:\\Users\\alice\\mycomput.dll"
+"#).unwrap();
+
+        let servers = inproc_servers(&file, "{58221C67-EA27-11CF-ADCF-00AA00A80033}");
+        let roots: Vec<&str> = servers.iter().map(|s| s.root.as_str()).collect();
+        assert_eq!(roots, CLASSES_ROOTS[..2]);
+        assert!(servers[0].effective && servers[0].is_user_override(&servers));
+        assert!(!servers[1].effective && !servers[1].is_user_override(&servers));
+        assert_eq!(inproc_server(&file, "{58221C67-EA27-11CF-ADCF-00AA00A80033}").unwrap(), r"C:\Users\alice\mycomput.dll");
+    }
+}
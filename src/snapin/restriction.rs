@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+use crate::source::{RegValue, RegistrySource};
+
+/// Where Group Policy restricts MMC, machine policy first since it wins over
+/// user policy.
+pub const POLICY_KEYS: [&str; 2] = [
+    r"HKEY_LOCAL_MACHINE\SOFTWARE\Policies\Microsoft\MMC",
+    r"HKEY_CURRENT_USER\Software\Policies\Microsoft\MMC",
+];
+
+/// Whether Group Policy lets a snap-in run, and which policy says so.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MMCRestriction {
+    pub blocked: bool,
+    /// The policy value that decided, like `...\MMC\{clsid}\Restrict_Run`.
+    /// `None` when no policy applies to the snap-in.
+    pub policy: Option<String>,
+    /// The policy value keeping users out of author mode, where snap-ins
+    /// are added to consoles, if there is one.
+    pub author_mode_policy: Option<String>,
+}
+
+impl MMCRestriction {
+    /// Reads the policies for the snap-in `clsid`. A snap-in's own
+    /// `Restrict_Run` decides; without one, `RestrictToPermittedSnapins`
+    /// blocks every snap-in that isn't explicitly permitted.
+    pub fn read(source: &dyn RegistrySource, clsid: &str) -> Self {
+        let mut restriction = MMCRestriction::default();
+
+        let explicit = POLICY_KEYS.iter().find_map(|key| {
+            let key = format!("{}\\{}", key, clsid);
+            dword(source, &key, "Restrict_Run").map(|n| (n != 0, format!("{}\\Restrict_Run", key)))
+        });
+        let permitted_only = || POLICY_KEYS.iter().find_map(|key| {
+            dword(source, key, "RestrictToPermittedSnapins").filter(|n| *n != 0).map(|_| (true, format!("{}\\RestrictToPermittedSnapins", key)))
+        });
+        if let Some((blocked, policy)) = explicit.or_else(permitted_only) {
+            restriction.blocked = blocked;
+            restriction.policy = Some(policy);
+        }
+
+        restriction.author_mode_policy = POLICY_KEYS.iter().find_map(|key| {
+            dword(source, key, "RestrictAuthorMode").filter(|n| *n != 0).map(|_| format!("{}\\RestrictAuthorMode", key))
+        });
+
+        restriction
+    }
+}
+
+fn dword(source: &dyn RegistrySource, key: &str, name: &str) -> Option<u32> {
+    source.values(key)?.into_iter().find_map(|(n, data)| match data {
+        RegValue::Dword(value) if n.eq_ignore_ascii_case(name) => Some(value),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::RegFile;
+
+    const COMPUTER_MANAGEMENT: &str = "{58221C67-EA27-11CF-ADCF-00AA00A80033}";
+    const EVENT_VIEWER: &str = "{975797FC-4E2A-11D0-B702-00C04FD8DBF7}";
+    const SERVICES: &str = "{58221C66-EA27-11CF-ADCF-00AA00A80033}";
+
+    #[test]
+    fn test_read() {
+        let file = RegFile::parse(r#"Windows Registry Editor Version 5.00
+
+[HKEY_CURRENT_USER\Software\Policies\Microsoft\MMC]
+"RestrictToPermittedSnapins"=dword:00000001
+"RestrictAuthorMode"=dword:00000001
+
+[HKEY_CURRENT_USER\Software\Policies\Microsoft\MMC\{58221C67-EA27-11CF-ADCF-00AA00A80033}]
+"Restrict_Run"=dword:00000000
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Policies\Microsoft\MMC\{975797FC-4E2A-11D0-B702-00C04FD8DBF7}]
+"Restrict_Run"=dword:00000001
+
+[HKEY_CURRENT_USER\Software\Policies\Microsoft\MMC\{975797FC-4E2A-11D0-B702-00C04FD8DBF7}]
+"Restrict_Run"=dword:00000000
+"#).unwrap();
+        let author_mode = Some(r"HKEY_CURRENT_USER\Software\Policies\Microsoft\MMC\RestrictAuthorMode".to_string());
+
+        assert_eq!(MMCRestriction::read(&file, COMPUTER_MANAGEMENT), MMCRestriction {
+            blocked: false,
+            policy: Some(format!(r"HKEY_CURRENT_USER\Software\Policies\Microsoft\MMC\{}\Restrict_Run", COMPUTER_MANAGEMENT)),
+            author_mode_policy: author_mode.clone(),
+        });
+        assert_eq!(MMCRestriction::read(&file, EVENT_VIEWER), MMCRestriction {
+            blocked: true,
+            policy: Some(format!(r"HKEY_LOCAL_MACHINE\SOFTWARE\Policies\Microsoft\MMC\{}\Restrict_Run", EVENT_VIEWER)),
+            author_mode_policy: author_mode.clone(),
+        });
+        assert_eq!(MMCRestriction::read(&file, SERVICES), MMCRestriction {
+            blocked: true,
+            policy: Some(r"HKEY_CURRENT_USER\Software\Policies\Microsoft\MMC\RestrictToPermittedSnapins".to_string()),
+            author_mode_policy: author_mode,
+        });
+    }
+
+    #[test]
+    fn test_no_policy() {
+        let file = RegFile::parse("Windows Registry Editor Version 5.00\n").unwrap();
+        assert_eq!(MMCRestriction::read(&file, SERVICES), MMCRestriction::default());
+    }
+}
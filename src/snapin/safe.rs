@@ -0,0 +1,69 @@
+use std::error::Error;
+
+use crate::nsi::IndirectString;
+use crate::pe::PeResources;
+use crate::source::RegistrySource;
+use super::module::{self, inproc_server, module_path};
+use super::{MMCSnapInAbout, Resolver};
+
+/// Resolves strings and About details by reading the snap-in's files instead
+/// of loading them. Indirect strings come from the DLL's string table, in
+/// the user's UI language from its `.mui` file when there is one, and
+/// the About details from the version resource and icon of the About
+/// object's in-proc server. No snap-in code runs, but nothing that only the
+/// About object itself knows (like its folder images) is available either.
+pub struct StaticResolver<'a> {
+    source: &'a dyn RegistrySource,
+    // The user's UI language tag, if it's one Windows ships in
+    language: Option<&'static str>,
+}
+
+impl<'a> StaticResolver<'a> {
+    /// `source` is where About CLSIDs are looked up.
+    pub fn new(source: &'a dyn RegistrySource) -> Self {
+        StaticResolver { source, language: module::ui_language() }
+    }
+}
+
+impl Resolver for StaticResolver<'_> {
+    fn load_string(&self, indirect: &IndirectString) -> Result<String, Box<dyn Error>> {
+        // Most system modules keep their strings in a satellite .mui
+        if let Some(Ok(string)) = self.language.map(|language| module::load_string_in(indirect, language)) {
+            return Ok(string);
+        }
+
+        let resources = PeResources::read(&module_path(&indirect.dllpath))?;
+        let id = u32::try_from(indirect.strid)?;
+        resources.string(id).ok_or_else(|| format!("string {} not found", id).into())
+    }
+
+    fn about(&self, clsid: &str) -> Result<MMCSnapInAbout, Box<dyn Error>> {
+        let resources = PeResources::read(&module_path(&inproc_server(self.source, clsid)?))?;
+        let version = resources.version_info().unwrap_or_default();
+
+        Ok(MMCSnapInAbout {
+            description: version.get("FileDescription").map(str::to_string),
+            provider: version.get("CompanyName").map(str::to_string),
+            version: version.get("ProductVersion").map(str::to_string).or(version.file_version.clone()),
+            #[cfg(windows)]
+            icon: resources.icon().and_then(super::win32::icon_from_resource),
+            #[cfg(windows)]
+            image: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::RegFile;
+
+    #[test]
+    fn test_about_unregistered() {
+        let file = RegFile::parse("Windows Registry Editor Version 5.00\n").unwrap();
+        let resolver = StaticResolver::new(&file);
+
+        let error = resolver.about("{58221C67-EA27-11CF-ADCF-00AA00A80033}").unwrap_err().to_string();
+        assert!(error.contains("no InprocServer32"), "{}", error);
+    }
+}
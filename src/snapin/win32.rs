@@ -0,0 +1,306 @@
+use std::error::Error;
+
+use log::trace;
+
+use windows::core::{IUnknown, Interface, GUID, PCWSTR, PWSTR};
+use windows::Win32::Foundation::COLORREF;
+use windows::Win32::Foundation::BOOL;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Foundation::{E_NOINTERFACE, REGDB_E_CLASSNOTREG};
+use windows::Win32::UI::WindowsAndMessaging::{CopyIcon, CopyImage, CreateIconFromResourceEx, DestroyIcon, GetIconInfo, HICON, ICONINFO, IMAGE_BITMAP, IMAGE_FLAGS, LR_DEFAULTCOLOR};
+use windows::Win32::{
+    System::{
+        Com::{
+            CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize,
+            CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED
+        },
+        LibraryLoader::LoadLibraryW,
+        Mmc::ISnapinAbout
+    },
+    UI::WindowsAndMessaging::LoadStringW,
+};
+use windows::Win32::Graphics::Gdi::{self, DeleteObject, HBITMAP};
+
+use crate::nsi::IndirectString;
+use super::{AboutError, AboutFailure, MMCSnapInAbout, Resolver};
+
+impl Drop for MMCSnapInAbout {
+    fn drop(&mut self) {
+        trace!("Drop {:?}", self);
+        if let Some(icon) = self.icon {
+            if !icon.is_invalid() {
+                let _ = unsafe { DestroyIcon(icon) };
+            }
+        }
+    }
+}
+#[derive(Clone, Default, Debug)]
+pub struct MMCSnapInImage {
+    pub small: HBITMAP,
+    pub small_open: HBITMAP,
+    pub large: HBITMAP,
+    pub mask: COLORREF,
+}
+
+impl Drop for MMCSnapInImage {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DeleteObject(self.small);
+            let _ = DeleteObject(self.small_open);
+            let _ = DeleteObject(self.large);
+        }
+    }
+}
+
+trait ToWide {
+    //fn to_wide(&self) -> Vec<u16>;
+    fn to_wide_null(&self) -> Vec<u16>;
+}
+
+impl ToWide for str {
+    /*
+    fn to_wide(&self) -> Vec<u16> {
+        self.encode_utf16().collect()
+    }
+    */
+
+    fn to_wide_null(&self) -> Vec<u16> {
+        self.encode_utf16().chain(Some(0)).collect()
+    }
+}
+
+/// Resolves strings and About objects by loading the snap-in's own DLLs on
+/// this machine. Safe to use from any thread: each thread that creates an
+/// About object joins its own apartment, and nothing COM hands back outlives
+/// the call.
+pub struct NativeResolver;
+
+// Keeps the thread in a COM apartment until it exits
+struct ComApartment(bool);
+
+impl Drop for ComApartment {
+    fn drop(&mut self) {
+        if self.0 {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+thread_local! {
+    static COM: ComApartment = ComApartment(unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) }.is_ok());
+}
+
+impl Resolver for NativeResolver {
+    fn load_string(&self, indirect: &IndirectString) -> Result<String, Box<dyn Error>> {
+        load_dll_string(&indirect.dllpath, indirect.strid)
+    }
+
+    fn about(&self, clsid: &str) -> Result<MMCSnapInAbout, Box<dyn Error>> {
+        let id = uuid::Uuid::parse_str(clsid)?;
+        let clsid = GUID::from_values(
+            id.as_fields().0,
+            id.as_fields().1,
+            id.as_fields().2,
+            *id.as_fields().3
+        );
+        COM.with(|_| ());
+        MMCSnapInAbout::try_from(clsid)
+    }
+}
+
+impl TryFrom<GUID> for MMCSnapInAbout {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: GUID) -> Result<Self, Self::Error> {
+        trace!("MMCSnapInAbout::TryFrom {:?}", value);
+        let mut snapin_about = MMCSnapInAbout::default();
+        unsafe {
+            match CoCreateInstance::<_, IUnknown>(&value, None, CLSCTX_INPROC_SERVER) {
+                Ok(iunk) => {
+                    let about = iunk.cast::<ISnapinAbout>();
+                    if let Ok(about) = about {
+                        trace!("\tCreated class instance");
+                        // Get description
+                        trace!("\tGetSnapinDescription()");
+                        let desc_ptr = about.GetSnapinDescription()?;
+                        if !desc_ptr.is_null() {
+                            let desc = desc_ptr.to_string()?;
+                            trace!("\tGot {} at {:#x}, freeing", desc, desc_ptr.0 as usize);
+                            CoTaskMemFree(Some(desc_ptr.0 as *const _));
+                            snapin_about.description = Some(desc);
+                        }
+
+                        // Get provider
+                        trace!("\tGetProvider()");
+                        let prov_ptr = about.GetProvider()?;
+                        if !prov_ptr.is_null() {
+                            let prov = prov_ptr.to_string()?;
+                            trace!("\tGot {} at {:#x}, freeing", prov, prov_ptr.0 as usize);
+                            CoTaskMemFree(Some(prov_ptr.0 as *const _));
+                            snapin_about.provider = Some(prov);
+                        }
+
+                        // Get version
+                        trace!("\tGetSnapinVersion()");
+                        let ver_ptr = about.GetSnapinVersion()?;
+                        if !ver_ptr.is_null() {
+                            trace!("\tGot {:?} from GetSnapinVersion()", ver_ptr);
+                            let ver = ver_ptr.to_string()?;
+                            trace!("\tGot {} at {:#x}, freeing", ver, ver_ptr.0 as usize);
+                            CoTaskMemFree(Some(ver_ptr.0 as *const _));
+                            snapin_about.version = Some(ver);
+                        }
+
+                        // Get icon
+                        let icon_ptr = about.GetSnapinImage()?;
+                        if !icon_ptr.is_invalid() {
+                            let my_icon = CopyIcon(icon_ptr)?;
+                            snapin_about.icon = Some(my_icon);
+                        }
+
+                        // Get images
+
+                        let mut small = Gdi::HBITMAP(0);
+                        let mut small_open = Gdi::HBITMAP(0);
+                        let mut large = Gdi::HBITMAP(0);
+                        let mut cmask = windows::Win32::Foundation::COLORREF(0);
+                        if about.GetStaticFolderImage(
+                            &mut small, 
+                            &mut small_open, 
+                            &mut large, 
+                            &mut cmask
+                        ).is_ok() {
+                            let image = MMCSnapInImage {
+                                small: copy_bitmap(small),
+                                small_open: copy_bitmap(small_open),
+                                large: copy_bitmap(large),
+                                mask: cmask,
+                            };
+
+                            snapin_about.image = Some(image);
+                        }
+                        // Ref counting?
+                        //drop(about_ref);
+
+                        // Return filled struct
+                        Ok(snapin_about)
+                    }
+                    else {
+                        Err(AboutError::new(AboutFailure::NoInterface, "ISnapInAbout not supported").into())
+                    }
+                }
+                // These won't change until the class is registered again
+                Err(e) if e.code() == REGDB_E_CLASSNOTREG => Err(AboutError::new(AboutFailure::ClassNotRegistered, e.message()).into()),
+                Err(e) if e.code() == E_NOINTERFACE => Err(AboutError::new(AboutFailure::NoInterface, e.message()).into()),
+                Err(e) => Err(e.into())
+            }
+        }
+        
+    }
+}
+
+fn load_dll_string(dll_path: &str, str_id: i32) -> Result<String, Box<dyn Error>> {
+    unsafe {
+        let h_module = LoadLibraryW(PCWSTR(dll_path.to_wide_null().as_ptr()))?;
+
+        let mut buffer: [u16; 260] = [0; 260];
+        let length = LoadStringW(h_module, str_id as u32, PWSTR(buffer.as_mut_ptr()), buffer.len() as i32);
+
+        if length == 0 {
+            return Err("Failed to load string resource".into());
+        }
+
+        let string = String::from_utf16_lossy(&buffer[..length as usize]);
+        Ok(string)
+    }
+}
+
+fn copy_bitmap(src: HBITMAP) -> HBITMAP {
+    let src_h = windows::Win32::Foundation::HANDLE(src.0);
+    let dst_h = unsafe { CopyImage(src_h, IMAGE_BITMAP, 48, 48, IMAGE_FLAGS(0)).unwrap() };
+    HBITMAP(dst_h.0)
+}
+
+impl MMCSnapInAbout {
+    /// The large image, with its mask color made transparent, or failing
+    /// that the icon, as top-down RGBA pixels: (width, height, pixels).
+    pub fn rgba(&self) -> Option<(u32, u32, Vec<u8>)> {
+        if let Some(image) = &self.image {
+            let (width, height, mut pixels) = bitmap_rgba(image.large)?;
+            let mask = image.mask.0.to_le_bytes();
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel[3] = if pixel[..3] == mask[..3] { 0 } else { 255 };
+            }
+            return Some((width, height, pixels));
+        }
+
+        let mut info = ICONINFO::default();
+        unsafe { GetIconInfo(self.icon?, &mut info) }.ok()?;
+        let color = bitmap_rgba(info.hbmColor);
+        let mask = bitmap_rgba(info.hbmMask);
+        unsafe {
+            let _ = DeleteObject(info.hbmColor);
+            let _ = DeleteObject(info.hbmMask);
+        }
+
+        // Icons without an alpha channel say what's transparent in the mask
+        let (width, height, mut pixels) = color?;
+        if pixels.chunks_exact(4).all(|p| p[3] == 0) {
+            let (_, _, mask) = mask?;
+            for (pixel, mask) in pixels.chunks_exact_mut(4).zip(mask.chunks_exact(4)) {
+                pixel[3] = if mask[0] == 0 { 255 } else { 0 };
+            }
+        }
+        Some((width, height, pixels))
+    }
+}
+
+// A bitmap's pixels as top-down RGBA
+fn bitmap_rgba(bitmap: HBITMAP) -> Option<(u32, u32, Vec<u8>)> {
+    if bitmap.is_invalid() {
+        return None;
+    }
+
+    unsafe {
+        let mut info = Gdi::BITMAP::default();
+        let size = std::mem::size_of::<Gdi::BITMAP>() as i32;
+        if Gdi::GetObjectW(bitmap, size, Some(&mut info as *mut _ as *mut std::ffi::c_void)) == 0 {
+            return None;
+        }
+        let (width, height) = (info.bmWidth, info.bmHeight.abs());
+
+        let mut header = Gdi::BITMAPINFO {
+            bmiHeader: Gdi::BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<Gdi::BITMAPINFOHEADER>() as u32,
+                biWidth: width,
+                // Negative for top-down rows
+                biHeight: -height,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: Gdi::BI_RGB.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+
+        let dc = Gdi::GetDC(HWND::default());
+        let lines = Gdi::GetDIBits(dc, bitmap, 0, height as u32, Some(pixels.as_mut_ptr() as *mut std::ffi::c_void), &mut header, Gdi::DIB_RGB_COLORS);
+        Gdi::ReleaseDC(HWND::default(), dc);
+        if lines == 0 {
+            return None;
+        }
+
+        // GDI's pixels are BGRA
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+        Some((width as u32, height as u32, pixels))
+    }
+}
+
+/// Makes an icon from the image data of an `RT_ICON` resource, without
+/// loading the module it came from.
+pub(super) fn icon_from_resource(data: &[u8]) -> Option<HICON> {
+    unsafe { CreateIconFromResourceEx(data, BOOL::from(true), 0x00030000, 0, 0, LR_DEFAULTCOLOR) }.ok()
+}
@@ -0,0 +1,422 @@
+use std::{collections::BTreeMap, error::Error, fmt::{self, Display}, fs, path::Path};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegValue {
+    String(String),
+    ExpandString(String),
+    MultiString(Vec<String>),
+    Dword(u32),
+    Qword(u64),
+    Binary(Vec<u8>),
+}
+
+impl RegValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            RegValue::String(s) | RegValue::ExpandString(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl Display for RegValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegValue::String(s) | RegValue::ExpandString(s) => write!(f, "{}", s),
+            RegValue::MultiString(strings) => write!(f, "{}", strings.join("\\0")),
+            RegValue::Dword(n) => write!(f, "{:#010x}", n),
+            RegValue::Qword(n) => write!(f, "{:#018x}", n),
+            RegValue::Binary(bytes) => {
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                write!(f, "{}", hex.join(","))
+            }
+        }
+    }
+}
+
+/// Somewhere to read registry keys from: the live registry, or a snapshot
+/// of it. Paths are full paths including the hive, e.g.
+/// `HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns`, and are matched
+/// case-insensitively. Sources are read from several threads at once.
+pub trait RegistrySource: Sync {
+    /// Names of the subkeys of `path`, or `None` if the key doesn't exist.
+    fn subkeys(&self, path: &str) -> Option<Vec<String>>;
+
+    /// Values of `path` as (name, data), or `None` if the key doesn't exist.
+    /// The default value has an empty name.
+    fn values(&self, path: &str) -> Option<Vec<(String, RegValue)>>;
+}
+
+/// Expands hive abbreviations and trims stray separators so paths from
+/// different sources compare equal.
+pub fn normalize_path(path: &str) -> String {
+    let path = path.trim().trim_matches('\\');
+    let (hive, rest) = path.split_once('\\').unwrap_or((path, ""));
+    let hive = match hive.to_ascii_uppercase().as_str() {
+        "HKLM" => "HKEY_LOCAL_MACHINE".to_string(),
+        "HKCU" => "HKEY_CURRENT_USER".to_string(),
+        "HKCR" => "HKEY_CLASSES_ROOT".to_string(),
+        "HKU" => "HKEY_USERS".to_string(),
+        "HKCC" => "HKEY_CURRENT_CONFIG".to_string(),
+        other => other.to_string(),
+    };
+
+    if rest.is_empty() {
+        hive
+    } else {
+        format!("{}\\{}", hive, rest)
+    }
+}
+
+#[derive(Debug, Default)]
+struct RegFileKey {
+    values: Vec<(String, RegValue)>,
+    // Lower case name to name as written
+    subkeys: BTreeMap<String, String>,
+}
+
+/// The keys and values of a `.reg` file, as exported by regedit.
+#[derive(Debug, Default)]
+pub struct RegFile {
+    // Keyed by lower case normalized path
+    keys: BTreeMap<String, RegFileKey>,
+}
+
+impl RegFile {
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let bytes = fs::read(path)?;
+
+        // regedit exports UTF-16 with a byte order mark, REGEDIT4 files are ANSI
+        let text = if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+            let wide: Vec<u16> = rest.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&wide)
+        } else {
+            let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(&bytes);
+            String::from_utf8_lossy(bytes).into_owned()
+        };
+
+        Ok(RegFile::parse(&text)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut file = RegFile::default();
+        // Lower case path of the section we're in, `None` before the first
+        // one or inside a `[-key]` deletion
+        let mut current: Option<String> = None;
+
+        for (line_number, line) in logical_lines(text) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                let path = line
+                    .strip_prefix('[')
+                    .and_then(|l| l.strip_suffix(']'))
+                    .ok_or(format!("line {}: unterminated key", line_number))?;
+                current = if path.starts_with('-') {
+                    None
+                } else {
+                    Some(file.insert_key(&normalize_path(path)))
+                };
+                continue;
+            }
+
+            if line.starts_with("Windows Registry Editor") || line == "REGEDIT4" {
+                continue;
+            }
+
+            let Some(key) = &current else { continue };
+            let (name, data) = parse_value_line(line).map_err(|e| format!("line {}: {}", line_number, e))?;
+            // A `-` deletes the value, which for a snapshot means it isn't there
+            if let Some(data) = data {
+                let values = &mut file.keys.get_mut(key).unwrap().values;
+                values.retain(|(n, _)| !n.eq_ignore_ascii_case(&name));
+                values.push((name, data));
+            }
+        }
+
+        Ok(file)
+    }
+
+    // Adds a key and all of its parents, returning its lookup path
+    fn insert_key(&mut self, path: &str) -> String {
+        let mut parent: Option<String> = None;
+        let mut lookup = String::new();
+
+        for part in path.split('\\').filter(|p| !p.is_empty()) {
+            if !lookup.is_empty() {
+                lookup.push('\\');
+            }
+            lookup.push_str(&part.to_lowercase());
+
+            self.keys.entry(lookup.clone()).or_default();
+            if let Some(parent) = &parent {
+                self.keys
+                    .get_mut(parent)
+                    .unwrap()
+                    .subkeys
+                    .entry(part.to_lowercase())
+                    .or_insert(part.to_string());
+            }
+            parent = Some(lookup.clone());
+        }
+
+        lookup
+    }
+
+    fn key(&self, path: &str) -> Option<&RegFileKey> {
+        self.keys.get(&normalize_path(path).to_lowercase())
+    }
+}
+
+impl RegistrySource for RegFile {
+    fn subkeys(&self, path: &str) -> Option<Vec<String>> {
+        Some(self.key(path)?.subkeys.values().cloned().collect())
+    }
+
+    fn values(&self, path: &str) -> Option<Vec<(String, RegValue)>> {
+        Some(self.key(path)?.values.clone())
+    }
+}
+
+// Joins lines continued with a trailing backslash, as regedit does for long
+// hex values, numbering each joined line by where it started.
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut pending: Option<(usize, String)> = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let (number, mut joined) = pending.take().unwrap_or((i + 1, String::new()));
+        let line = if joined.is_empty() { line } else { line.trim_start() };
+
+        if let Some(continued) = line.strip_suffix('\\').filter(|_| !line.trim_start().starts_with('[')) {
+            if is_hex_continuation(&joined, continued) {
+                joined.push_str(continued);
+                pending = Some((number, joined));
+                continue;
+            }
+        }
+
+        joined.push_str(line);
+        lines.push((number, joined));
+    }
+
+    if let Some(line) = pending {
+        lines.push(line);
+    }
+
+    lines
+}
+
+// Only hex data is ever continued; a string value can legitimately end in a
+// backslash (`"C:\\"`), so don't join those.
+fn is_hex_continuation(joined: &str, line: &str) -> bool {
+    let full = format!("{}{}", joined, line);
+    match full.split_once('=') {
+        Some((_, data)) => data.trim_start().starts_with("hex"),
+        None => false,
+    }
+}
+
+fn parse_value_line(line: &str) -> Result<(String, Option<RegValue>), String> {
+    let (name, rest) = if let Some(rest) = line.strip_prefix('@') {
+        (String::new(), rest)
+    } else if line.starts_with('"') {
+        parse_quoted(line)?
+    } else {
+        return Err(format!("expected a value name, found '{}'", line));
+    };
+
+    let data = rest
+        .trim_start()
+        .strip_prefix('=')
+        .ok_or("expected '=' after value name")?
+        .trim();
+
+    if data == "-" {
+        return Ok((name, None));
+    }
+
+    if data.starts_with('"') {
+        let (s, _) = parse_quoted(data)?;
+        return Ok((name, Some(RegValue::String(s))));
+    }
+
+    if let Some(hex) = data.strip_prefix("dword:") {
+        let n = u32::from_str_radix(hex.trim(), 16).map_err(|_| format!("invalid dword '{}'", hex))?;
+        return Ok((name, Some(RegValue::Dword(n))));
+    }
+
+    let (kind, hex) = if let Some(hex) = data.strip_prefix("hex:") {
+        (3, hex)
+    } else if let Some(rest) = data.strip_prefix("hex(") {
+        let (kind, hex) = rest.split_once("):").ok_or("invalid hex value type")?;
+        (u32::from_str_radix(kind, 16).map_err(|_| format!("invalid hex value type '{}'", kind))?, hex)
+    } else {
+        return Err(format!("unsupported value data '{}'", data));
+    };
+
+    let bytes = hex
+        .split(',')
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(|b| u8::from_str_radix(b, 16).map_err(|_| format!("invalid hex byte '{}'", b)))
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    let value = match kind {
+        // REG_EXPAND_SZ and REG_MULTI_SZ are UTF-16, NUL terminated
+        2 => RegValue::ExpandString(utf16_strings(&bytes).into_iter().next().unwrap_or_default()),
+        7 => RegValue::MultiString(utf16_strings(&bytes)),
+        4 if bytes.len() == 4 => RegValue::Dword(u32::from_le_bytes(bytes.try_into().unwrap())),
+        0xb if bytes.len() == 8 => RegValue::Qword(u64::from_le_bytes(bytes.try_into().unwrap())),
+        _ => RegValue::Binary(bytes),
+    };
+
+    Ok((name, Some(value)))
+}
+
+// Parses a leading "quoted" string with .reg escapes, returning it and the
+// rest of the line.
+fn parse_quoted(s: &str) -> Result<(String, &str), String> {
+    let mut out = String::new();
+    let mut chars = s.char_indices().skip(1);
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, 'n')) => out.push('\n'),
+                Some((_, c)) => out.push(c),
+                None => break,
+            },
+            '"' => return Ok((out, &s[i + 1..])),
+            c => out.push(c),
+        }
+    }
+
+    Err("unterminated string".to_string())
+}
+
+fn utf16_strings(bytes: &[u8]) -> Vec<String> {
+    let wide: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    wide.split(|&c| c == 0)
+        .filter(|s| !s.is_empty())
+        .map(String::from_utf16_lossy)
+        .collect()
+}
+
+#[cfg(windows)]
+pub use live::LiveRegistry;
+
+#[cfg(windows)]
+mod live {
+    use registry::{Data, Hive, Security};
+
+    use super::{normalize_path, RegValue, RegistrySource};
+
+    /// The registry of the machine we're running on.
+    pub struct LiveRegistry;
+
+    fn open(path: &str) -> Option<registry::RegKey> {
+        let path = normalize_path(path);
+        let (hive, subkey) = path.split_once('\\').unwrap_or((&path, ""));
+        let hive = match hive {
+            "HKEY_LOCAL_MACHINE" => Hive::LocalMachine,
+            "HKEY_CURRENT_USER" => Hive::CurrentUser,
+            "HKEY_CLASSES_ROOT" => Hive::ClassesRoot,
+            "HKEY_USERS" => Hive::Users,
+            "HKEY_CURRENT_CONFIG" => Hive::CurrentConfig,
+            _ => return None,
+        };
+        hive.open(subkey, Security::Read).ok()
+    }
+
+    impl RegistrySource for LiveRegistry {
+        fn subkeys(&self, path: &str) -> Option<Vec<String>> {
+            let key = open(path)?;
+            Some(key.keys().filter_map(|k| k.ok()).map(|k| k.to_string()).collect())
+        }
+
+        fn values(&self, path: &str) -> Option<Vec<(String, RegValue)>> {
+            let key = open(path)?;
+            let values = key
+                .values()
+                .filter_map(|v| v.ok())
+                .map(|v| {
+                    let data = match v.data() {
+                        Data::String(s) => RegValue::String(s.to_string_lossy()),
+                        Data::ExpandString(s) => RegValue::ExpandString(s.to_string_lossy()),
+                        Data::MultiString(s) => RegValue::MultiString(s.iter().map(|s| s.to_string_lossy()).collect()),
+                        Data::U32(n) | Data::U32BE(n) => RegValue::Dword(*n),
+                        Data::U64(n) => RegValue::Qword(*n),
+                        Data::Binary(bytes) => RegValue::Binary(bytes.clone()),
+                        _ => RegValue::Binary(Vec::new()),
+                    };
+                    (v.name().to_string_lossy(), data)
+                })
+                .collect();
+            Some(values)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(file: &RegFile, path: &str, name: &str) -> Option<RegValue> {
+        file.values(path)?
+            .into_iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, data)| data)
+    }
+
+    const TEST_REG: &str = r#"Windows Registry Editor Version 5.00
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}]
+"NameString"="Computer Management"
+"NameStringIndirect"="@%SystemRoot%\\system32\\mycomput.dll,-106"
+"About"="{58221C67-EA27-11CF-ADCF-00AA00A80033}"
+"ModuleName"=hex(2):25,00,53,00,79,00,73,00,74,00,65,00,6d,00,52,00,6f,00,6f,\
+  00,74,00,25,00,00,00
+"Flags"=dword:0000000a
+"Removed"=-
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}\StandAlone]
+
+[-HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{00000000-0000-0000-0000-000000000000}]
+"Ignored"="value"
+"#;
+
+    #[test]
+    fn test_parse_reg_file() {
+        let file = RegFile::parse(TEST_REG).unwrap();
+        let key = r"HKLM\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}";
+
+        assert_eq!(value(&file, key, "namestring"), Some(RegValue::String("Computer Management".into())));
+        assert_eq!(value(&file, key, "NameStringIndirect"), Some(RegValue::String(r"@%SystemRoot%\system32\mycomput.dll,-106".into())));
+        assert_eq!(value(&file, key, "ModuleName"), Some(RegValue::ExpandString("%SystemRoot%".into())));
+        assert_eq!(value(&file, key, "Flags"), Some(RegValue::Dword(10)));
+        assert_eq!(value(&file, key, "Removed"), None);
+        assert_eq!(file.subkeys(key), Some(vec!["StandAlone".to_string()]));
+    }
+
+    #[test]
+    fn test_reg_file_parent_keys() {
+        let file = RegFile::parse(TEST_REG).unwrap();
+
+        assert_eq!(
+            file.subkeys(r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns"),
+            Some(vec!["{58221C67-EA27-11CF-ADCF-00AA00A80033}".to_string()])
+        );
+        assert_eq!(file.subkeys(r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\NodeTypes"), None);
+    }
+
+    #[test]
+    fn test_parse_reg_file_invalid_value() {
+        let result = RegFile::parse("[HKEY_LOCAL_MACHINE\\SOFTWARE]\n\"Value\"=dword:xyz\n");
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::{self, Display};
+
+use serde::Serialize;
+
+use crate::snapin::MMCSnapIn;
+
+const NO_PROVIDER: &str = "(no provider)";
+const NO_MODULE: &str = "(no module registered)";
+
+/// How many snap-ins share a provider or module, and which.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+    pub snapins: Vec<String>,
+}
+
+/// Counts over an inventory, to see at a glance where its snap-ins come
+/// from and how many are in trouble.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub total: usize,
+    /// Most snap-ins first, then by name.
+    pub by_provider: Vec<Count>,
+    pub by_module: Vec<Count>,
+    pub native: usize,
+    pub managed: usize,
+    pub standalone: usize,
+    pub extension_only: usize,
+    /// Neither blocked nor with diagnostics.
+    pub healthy: usize,
+    /// Not blocked, but something about them couldn't be read or resolved.
+    pub with_diagnostics: usize,
+    pub blocked: usize,
+}
+
+impl Summary {
+    pub fn new(snapins: &[MMCSnapIn]) -> Self {
+        let mut summary = Summary { total: snapins.len(), ..Default::default() };
+
+        for snapin in snapins {
+            let provider = match snapin.get_provider() {
+                "" => NO_PROVIDER.to_string(),
+                provider => provider.to_string(),
+            };
+            count(&mut summary.by_provider, provider, &snapin.clsid);
+            count(&mut summary.by_module, snapin.module().unwrap_or(NO_MODULE.to_string()), &snapin.clsid);
+
+            match snapin.is_managed() {
+                true => summary.managed += 1,
+                false => summary.native += 1,
+            }
+            match snapin.standalone {
+                true => summary.standalone += 1,
+                false => summary.extension_only += 1,
+            }
+            if snapin.restriction.blocked {
+                summary.blocked += 1;
+            } else if !snapin.diagnostics.is_empty() {
+                summary.with_diagnostics += 1;
+            } else {
+                summary.healthy += 1;
+            }
+        }
+
+        for counts in [&mut summary.by_provider, &mut summary.by_module] {
+            counts.sort_by_key(|c| (usize::MAX - c.count, c.name.to_lowercase()));
+        }
+        summary
+    }
+}
+
+// Counts `clsid` under `name`, matched without regard to case like paths
+fn count(counts: &mut Vec<Count>, name: String, clsid: &str) {
+    match counts.iter_mut().find(|c| c.name.eq_ignore_ascii_case(&name)) {
+        Some(c) => {
+            c.count += 1;
+            c.snapins.push(clsid.to_string());
+        }
+        None => counts.push(Count { name, count: 1, snapins: vec![clsid.to_string()] }),
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} snap-ins", self.total)?;
+        writeln!(f, "  {} native, {} managed", self.native, self.managed)?;
+        writeln!(f, "  {} standalone, {} extension only", self.standalone, self.extension_only)?;
+        writeln!(f, "  {} healthy, {} with diagnostics, {} blocked by policy", self.healthy, self.with_diagnostics, self.blocked)?;
+
+        for (title, counts) in [("By provider", &self.by_provider), ("By module", &self.by_module)] {
+            writeln!(f, "\n{}:", title)?;
+            for c in counts {
+                writeln!(f, "  {:>4}  {}", c.count, c.name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapin::{MMCRestriction, MMCServer};
+
+    fn snapin(clsid: &str, provider: Option<&str>, module: &str) -> MMCSnapIn {
+        MMCSnapIn {
+            clsid: clsid.into(),
+            providerstringindirect: provider.map(str::to_string),
+            servers: vec![MMCServer {
+                clsid: clsid.into(),
+                root: r"HKEY_LOCAL_MACHINE\SOFTWARE\Classes\CLSID".into(),
+                path: module.into(),
+                effective: true,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut snapins = vec![
+            snapin("{58221C67-EA27-11CF-ADCF-00AA00A80033}", Some("Microsoft Corporation"), r"%SystemRoot%\System32\mycomput.dll"),
+            snapin("{975797FC-4E2A-11D0-B702-00C04FD8DBF7}", Some("Microsoft Corporation"), r"%SystemRoot%\system32\els.dll"),
+            snapin("{58221C66-EA27-11CF-ADCF-00AA00A80033}", None, r"%SYSTEMROOT%\System32\MYCOMPUT.DLL"),
+            MMCSnapIn {
+                clsid: "FX:{b05566ad-fe9c-4363-be05-7a4cbb7cb510}".into(),
+                providerstringindirect: Some("Microsoft Corporation".into()),
+                application_base: Some(r"C:\Windows\system32\".into()),
+                module_name: Some("EventViewer.dll".into()),
+                ..Default::default()
+            },
+        ];
+        snapins[0].standalone = true;
+        snapins[1].diagnostics.push("About: not resolved".into());
+        snapins[2].restriction = MMCRestriction { blocked: true, ..Default::default() };
+        snapins[2].diagnostics.push("About: not resolved".into());
+
+        let summary = Summary::new(&snapins);
+        assert_eq!((summary.total, summary.native, summary.managed), (4, 3, 1));
+        assert_eq!((summary.standalone, summary.extension_only), (1, 3));
+        assert_eq!((summary.healthy, summary.with_diagnostics, summary.blocked), (2, 1, 1));
+
+        let providers: Vec<(&str, usize)> = summary.by_provider.iter().map(|c| (c.name.as_str(), c.count)).collect();
+        assert_eq!(providers, [("Microsoft Corporation", 3), ("(no provider)", 1)]);
+        let modules: Vec<(&str, usize)> = summary.by_module.iter().map(|c| (c.name.as_str(), c.count)).collect();
+        assert_eq!(modules, [
+            (r"%SystemRoot%\System32\mycomput.dll", 2),
+            (r"%SystemRoot%\system32\els.dll", 1),
+            (r"C:\Windows\system32\EventViewer.dll", 1),
+        ]);
+        assert_eq!(summary.by_module[0].snapins, ["{58221C67-EA27-11CF-ADCF-00AA00A80033}", "{58221C66-EA27-11CF-ADCF-00AA00A80033}"]);
+    }
+}
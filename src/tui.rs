@@ -0,0 +1,378 @@
+use std::error::Error;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::launch::{self, ProcessLauncher};
+use crate::snapin::MMCSnapIn;
+use crate::view::{self, Column, SnapInListModel};
+
+const HELP: &str = "Up/Down/PgUp/PgDn move  Tab next pane  type to filter, or a query like standalone && !about  \
+                    Enter open in MMC  Esc clear filter/quit  ^A extension-only  ^S sort column  ^R reverse";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    List,
+    Details,
+    Extensions,
+}
+
+/// Text-mode counterpart of `MyWindow`, for when there's no desktop to show
+/// it on. Draws the same `SnapInListModel`.
+struct Browser {
+    model: SnapInListModel,
+    focus: Pane,
+    table: TableState,
+    details_scroll: u16,
+    extensions_scroll: u16,
+    // Rows visible in the list pane when it was last drawn
+    page: usize,
+    // Shown instead of the help until the next key
+    status: Option<String>,
+}
+
+pub fn run(snapins: Vec<MMCSnapIn>) -> Result<(), Box<dyn Error>> {
+    let mut terminal = ratatui::init();
+    let result = Browser::new(snapins).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl Browser {
+    fn new(snapins: Vec<MMCSnapIn>) -> Self {
+        let mut browser = Browser {
+            model: SnapInListModel::new(snapins),
+            focus: Pane::List,
+            table: TableState::default(),
+            details_scroll: 0,
+            extensions_scroll: 0,
+            page: 1,
+            status: None,
+        };
+        browser.select(0);
+        browser
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn Error>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Handles a key press, returning false to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let page = self.page as isize;
+        self.status = None;
+
+        match key.code {
+            KeyCode::Char('c') if ctrl => return false,
+            KeyCode::Char('a') if ctrl => {
+                self.model.set_show_all(!self.model.show_all());
+                self.select(0);
+            }
+            KeyCode::Char('s') if ctrl => {
+                let (column, _) = self.model.sort();
+                let next = Column::ALL.iter().position(|&c| c == column).map_or(0, |i| i + 1);
+                self.model.sort_by(Column::ALL[next % Column::ALL.len()]);
+                self.select(0);
+            }
+            KeyCode::Char('r') if ctrl => {
+                self.model.sort_by(self.model.sort().0);
+                self.select(0);
+            }
+            KeyCode::Char(c) if !ctrl => {
+                let filter = format!("{}{}", self.model.filter(), c);
+                self.set_filter(&filter);
+            }
+            KeyCode::Backspace => {
+                let mut filter = self.model.filter().to_string();
+                filter.pop();
+                self.set_filter(&filter);
+            }
+            KeyCode::Esc => {
+                if self.model.filter().is_empty() {
+                    return false;
+                }
+                self.set_filter("");
+            }
+            KeyCode::Enter => {
+                if let Some(snapin) = self.model.selected() {
+                    self.status = Some(match launch::open_in_mmc(&[snapin], &ProcessLauncher) {
+                        Ok(path) => format!("Opened {} in MMC", path.display()),
+                        Err(e) => e.to_string(),
+                    });
+                }
+            }
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Pane::List => Pane::Details,
+                    Pane::Details => Pane::Extensions,
+                    Pane::Extensions => Pane::List,
+                };
+            }
+            KeyCode::Up => self.scroll(-1),
+            KeyCode::Down => self.scroll(1),
+            KeyCode::PageUp => self.scroll(-page),
+            KeyCode::PageDown => self.scroll(page),
+            KeyCode::Home => self.scroll(isize::MIN / 2),
+            KeyCode::End => self.scroll(isize::MAX / 2),
+            _ => {}
+        }
+
+        true
+    }
+
+    fn set_filter(&mut self, filter: &str) {
+        self.model.set_filter(filter);
+        self.select(0);
+    }
+
+    // Moves the selection or scrolls the focused text pane by `delta` lines
+    fn scroll(&mut self, delta: isize) {
+        let by = |scroll: u16| (scroll as isize + delta).clamp(0, u16::MAX as isize) as u16;
+        match self.focus {
+            Pane::List => {
+                let current = self.model.selected_row().unwrap_or(0) as isize;
+                self.select(current.saturating_add(delta).max(0) as usize);
+            }
+            Pane::Details => self.details_scroll = by(self.details_scroll),
+            Pane::Extensions => self.extensions_scroll = by(self.extensions_scroll),
+        }
+    }
+
+    // Selects `row`, or the last row if it's past the end
+    fn select(&mut self, row: usize) {
+        let rows = self.model.rows().len();
+        let row = (rows > 0).then(|| row.min(rows - 1));
+        if row != self.model.selected_row() {
+            self.details_scroll = 0;
+            self.extensions_scroll = 0;
+        }
+        self.model.select(row);
+        self.table.select(row);
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [filter_area, main_area, help_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+        let [list_area, right_area] = Layout::horizontal([
+            Constraint::Percentage(55),
+            Constraint::Percentage(45),
+        ]).areas(main_area);
+        let [details_area, extensions_area] = Layout::vertical([
+            Constraint::Percentage(60),
+            Constraint::Percentage(40),
+        ]).areas(right_area);
+
+        let mut filter_block = Block::bordered().title(
+            if self.model.show_all() { "Filter (all snap-ins)" } else { "Filter (standalone snap-ins)" }
+        );
+        // A query that doesn't parse gets its caret line in the border,
+        // right under the filter
+        if let Some(caret) = self.model.filter_error().and_then(|e| e.lines().nth(1)) {
+            filter_block = filter_block.title_bottom(Line::from(caret.to_string()).style(Style::new().fg(Color::Red)));
+        }
+        frame.render_widget(Paragraph::new(self.model.filter()).block(filter_block), filter_area);
+        frame.set_cursor_position((
+            filter_area.x + 1 + self.model.filter().chars().count() as u16,
+            filter_area.y + 1,
+        ));
+
+        self.draw_list(frame, list_area);
+
+        let selected = self.model.selected();
+        let details = selected.map(view::details_text).unwrap_or_default();
+        let extensions = match selected.map(|s| view::extension_tree(s, self.model.snapins())) {
+            Some(tree) if tree.is_empty() => "No node types".to_string(),
+            tree => tree.unwrap_or_default(),
+        };
+
+        let details = Paragraph::new(details)
+            .block(self.pane_block(Pane::Details, "Details".to_string()))
+            .wrap(Wrap { trim: false })
+            .scroll((self.details_scroll, 0));
+        frame.render_widget(details, details_area);
+
+        let extensions = Paragraph::new(extensions)
+            .block(self.pane_block(Pane::Extensions, "Extensions".to_string()))
+            .scroll((self.extensions_scroll, 0));
+        frame.render_widget(extensions, extensions_area);
+
+        let help = match &self.status {
+            Some(status) => Line::from(status.as_str()),
+            None => Line::from(HELP).style(Style::new().add_modifier(Modifier::DIM)),
+        };
+        frame.render_widget(help, help_area);
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect) {
+        let (sort_column, ascending) = self.model.sort();
+        let header = Row::new(Column::ALL.map(|c| {
+            match c == sort_column {
+                true => format!("{} {}", c.title(), if ascending { "^" } else { "v" }),
+                false => c.title().to_string(),
+            }
+        })).style(Style::new().add_modifier(Modifier::BOLD));
+
+        let rows = self.model.rows().iter().map(|&i| {
+            let snapin = &self.model.snapins()[i];
+            Row::new(Column::ALL.map(|c| c.text(snapin).to_string()))
+        });
+
+        let title = format!("Snap-ins ({}/{})", self.model.rows().len(), self.model.snapins().len());
+        let table = Table::new(rows, [Constraint::Fill(2), Constraint::Fill(3), Constraint::Length(38)])
+            .header(header)
+            .block(self.pane_block(Pane::List, title))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        // Borders and header
+        self.page = area.height.saturating_sub(3).max(1) as usize;
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn pane_block(&self, pane: Pane, title: String) -> Block<'static> {
+        let block = Block::bordered().title(title);
+        if self.focus == pane {
+            block.border_style(Style::new().add_modifier(Modifier::BOLD))
+        } else {
+            block
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn browser() -> Browser {
+        let snapin = |clsid: &str, name: &str| MMCSnapIn { clsid: clsid.into(), namestring: Some(name.into()), standalone: true, ..Default::default() };
+        Browser::new(vec![
+            snapin("{975797FC-4E2A-11D0-B702-00C04FD8DBF7}", "Event Viewer"),
+            snapin("{58221C67-EA27-11CF-ADCF-00AA00A80033}", "Computer Management"),
+            snapin("{2FAEBFA2-3F1A-11D0-8C65-00C04FD8FECB}", "DNS"),
+        ])
+    }
+
+    fn press(browser: &mut Browser, code: KeyCode) -> bool {
+        browser.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn type_text(browser: &mut Browser, text: &str) {
+        for c in text.chars() {
+            press(browser, KeyCode::Char(c));
+        }
+    }
+
+    fn selected_name(browser: &Browser) -> Option<&str> {
+        browser.model.selected().map(MMCSnapIn::get_name)
+    }
+
+    #[test]
+    fn test_selection_stays_in_list() {
+        let mut browser = browser();
+        assert_eq!(browser.model.selected_row(), Some(0));
+
+        press(&mut browser, KeyCode::Up);
+        assert_eq!(browser.model.selected_row(), Some(0));
+
+        press(&mut browser, KeyCode::Down);
+        press(&mut browser, KeyCode::Down);
+        press(&mut browser, KeyCode::Down);
+        assert_eq!(browser.model.selected_row(), Some(2));
+        assert_eq!(browser.table.selected(), Some(2));
+
+        press(&mut browser, KeyCode::Home);
+        assert_eq!(browser.model.selected_row(), Some(0));
+        press(&mut browser, KeyCode::End);
+        assert_eq!(browser.model.selected_row(), Some(2));
+
+        browser.page = 10;
+        press(&mut browser, KeyCode::PageUp);
+        assert_eq!(browser.model.selected_row(), Some(0));
+        press(&mut browser, KeyCode::PageDown);
+        assert_eq!(browser.model.selected_row(), Some(2));
+    }
+
+    #[test]
+    fn test_filter_edits() {
+        let mut browser = browser();
+        press(&mut browser, KeyCode::End);
+        assert_eq!(selected_name(&browser), Some("Event Viewer"));
+
+        // The list shrinks under the selection
+        type_text(&mut browser, "dns");
+        assert_eq!(browser.model.rows().len(), 1);
+        assert_eq!(selected_name(&browser), Some("DNS"));
+
+        type_text(&mut browser, "x");
+        assert!(browser.model.rows().is_empty());
+        assert_eq!(browser.model.selected_row(), None);
+        assert_eq!(browser.table.selected(), None);
+
+        press(&mut browser, KeyCode::Backspace);
+        assert_eq!(browser.model.filter(), "dns");
+        assert_eq!(selected_name(&browser), Some("DNS"));
+
+        // Esc clears the filter, then quits
+        assert!(press(&mut browser, KeyCode::Esc));
+        assert_eq!(browser.model.filter(), "");
+        assert_eq!(browser.model.rows().len(), 3);
+        assert!(!press(&mut browser, KeyCode::Esc));
+    }
+
+    #[test]
+    fn test_panes() {
+        let mut browser = browser();
+
+        press(&mut browser, KeyCode::Tab);
+        assert_eq!(browser.focus, Pane::Details);
+        press(&mut browser, KeyCode::Down);
+        press(&mut browser, KeyCode::Down);
+        assert_eq!(browser.details_scroll, 2);
+        assert_eq!(browser.model.selected_row(), Some(0));
+
+        press(&mut browser, KeyCode::Tab);
+        assert_eq!(browser.focus, Pane::Extensions);
+        press(&mut browser, KeyCode::Up);
+        assert_eq!(browser.extensions_scroll, 0);
+        press(&mut browser, KeyCode::Down);
+        assert_eq!(browser.extensions_scroll, 1);
+
+        // Another snap-in starts at the top of its panes
+        press(&mut browser, KeyCode::Tab);
+        assert_eq!(browser.focus, Pane::List);
+        press(&mut browser, KeyCode::Down);
+        assert_eq!((browser.details_scroll, browser.extensions_scroll), (0, 0));
+    }
+
+    #[test]
+    fn test_empty_list() {
+        let mut browser = Browser::new(Vec::new());
+        assert_eq!(browser.model.selected_row(), None);
+
+        for code in [KeyCode::Down, KeyCode::Up, KeyCode::End, KeyCode::PageDown, KeyCode::Enter, KeyCode::Backspace] {
+            assert!(press(&mut browser, code));
+        }
+        assert_eq!(browser.model.selected_row(), None);
+        assert_eq!(browser.table.selected(), None);
+        assert!(browser.status.is_none());
+
+        press(&mut browser, KeyCode::Char('a'));
+        assert!(browser.model.rows().is_empty());
+        assert_eq!(browser.model.selected_row(), None);
+    }
+}
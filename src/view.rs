@@ -0,0 +1,491 @@
+use std::{cmp::Ordering, fmt::Write};
+
+use crate::query::{self, Query};
+use crate::snapin::{normalize_clsid, MMCExtension, MMCSnapIn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Name,
+    Description,
+    Clsid,
+}
+
+impl Column {
+    pub const ALL: [Column; 3] = [Column::Name, Column::Description, Column::Clsid];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Column::Name => "Name",
+            Column::Description => "Description",
+            Column::Clsid => "CLSID",
+        }
+    }
+
+    pub fn text<'a>(&self, snapin: &'a MMCSnapIn) -> &'a str {
+        match self {
+            Column::Name => snapin.get_name(),
+            Column::Description => snapin.get_description(),
+            Column::Clsid => &snapin.clsid,
+        }
+    }
+}
+
+/// What to show as a snap-in's icon: the folder image from its About
+/// object, failing that its icon, failing that a placeholder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconChoice {
+    Placeholder,
+    Image,
+    Icon,
+}
+
+impl IconChoice {
+    pub fn for_snapin(snapin: &MMCSnapIn) -> Self {
+        match &snapin.about {
+            Some(about) if about.has_image() => IconChoice::Image,
+            Some(about) if about.has_icon() => IconChoice::Icon,
+            _ => IconChoice::Placeholder,
+        }
+    }
+}
+
+/// Assigns image list indices for a list of icon choices. The placeholder is
+/// index 0 and every image or icon gets the next index, in order.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn icon_indices(choices: &[IconChoice]) -> Vec<u32> {
+    let mut next = 1;
+    choices
+        .iter()
+        .map(|choice| match choice {
+            IconChoice::Placeholder => 0,
+            _ => {
+                next += 1;
+                next - 1
+            }
+        })
+        .collect()
+}
+
+/// The state behind the snap-in list: which snap-ins are shown, in what
+/// order, and which one is selected. Renderers only need to draw `rows()`.
+pub struct SnapInListModel {
+    snapins: Vec<MMCSnapIn>,
+    // Only the Windows GUI shows icons
+    #[cfg_attr(not(windows), allow(dead_code))]
+    icons: Vec<IconChoice>,
+    filter: String,
+    // Why the filter, written as a query, doesn't parse
+    filter_error: Option<String>,
+    show_all: bool,
+    sort_column: Column,
+    sort_ascending: bool,
+    // Snap-in index for each visible row, in display order
+    rows: Vec<usize>,
+    selected: Option<usize>,
+}
+
+impl SnapInListModel {
+    pub fn new(snapins: Vec<MMCSnapIn>) -> Self {
+        let icons = snapins.iter().map(IconChoice::for_snapin).collect();
+        let mut model = SnapInListModel {
+            snapins,
+            icons,
+            filter: String::new(),
+            filter_error: None,
+            show_all: false,
+            sort_column: Column::Name,
+            sort_ascending: true,
+            rows: Vec::new(),
+            selected: None,
+        };
+        model.update_rows();
+        model
+    }
+
+    pub fn snapins(&self) -> &[MMCSnapIn] {
+        &self.snapins
+    }
+
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn icons(&self) -> &[IconChoice] {
+        &self.icons
+    }
+
+    pub fn rows(&self) -> &[usize] {
+        &self.rows
+    }
+
+    pub fn row(&self, row: usize) -> Option<&MMCSnapIn> {
+        self.rows.get(row).map(|&i| &self.snapins[i])
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    /// Filters the snap-ins by text in their name, description, CLSID,
+    /// provider or module, or by a query like `standalone && !about` when
+    /// the filter has an operator in it, see `query::looks_like_query`.
+    pub fn set_filter(&mut self, filter: &str) {
+        self.filter = filter.to_string();
+        self.update_rows();
+    }
+
+    /// Where the filter goes wrong as a query, with a caret under the
+    /// problem. Until it's fixed the filter is matched as plain text, so
+    /// names like `AT&T` still find something.
+    pub fn filter_error(&self) -> Option<&str> {
+        self.filter_error.as_deref()
+    }
+
+    pub fn show_all(&self) -> bool {
+        self.show_all
+    }
+
+    /// Whether extension-only (non-standalone) snap-ins are listed too.
+    pub fn set_show_all(&mut self, show_all: bool) {
+        self.show_all = show_all;
+        self.update_rows();
+    }
+
+    /// The column rows are sorted by, and whether ascending.
+    pub fn sort(&self) -> (Column, bool) {
+        (self.sort_column, self.sort_ascending)
+    }
+
+    /// Sorts by `column`, or reverses the order if already sorted by it.
+    pub fn sort_by(&mut self, column: Column) {
+        if self.sort_column == column {
+            self.sort_ascending = !self.sort_ascending;
+        } else {
+            self.sort_column = column;
+            self.sort_ascending = true;
+        }
+        self.update_rows();
+    }
+
+    pub fn selected(&self) -> Option<&MMCSnapIn> {
+        self.selected.and_then(|row| self.row(row))
+    }
+
+    pub fn selected_row(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn select(&mut self, row: Option<usize>) {
+        self.selected = row.filter(|&row| row < self.rows.len());
+    }
+
+    /// Drops the snap-ins with the `removed` CLSIDs, and replaces or adds
+    /// `updated` ones. The selection stays on the same snap-in if it's still
+    /// listed.
+    // Only the Windows GUI watches for changes
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn update(&mut self, removed: &[String], updated: Vec<MMCSnapIn>) {
+        let key = |s: &MMCSnapIn| normalize_clsid(&s.clsid).unwrap_or(s.clsid.clone());
+        let selected = self.selected().map(key);
+
+        self.snapins.retain(|s| !removed.contains(&key(s)));
+        for snapin in updated {
+            match self.snapins.iter().position(|s| key(s) == key(&snapin)) {
+                Some(i) => self.snapins[i] = snapin,
+                None => self.snapins.push(snapin),
+            }
+        }
+        self.icons = self.snapins.iter().map(IconChoice::for_snapin).collect();
+
+        self.update_rows();
+        self.selected = selected.and_then(|clsid| self.rows.iter().position(|&i| key(&self.snapins[i]) == clsid));
+    }
+
+    fn update_rows(&mut self) {
+        let filter = self.filter.to_lowercase();
+        let snapins = &self.snapins;
+
+        let query = match query::looks_like_query(&self.filter) {
+            true => Query::parse(&self.filter).map(Some).map_err(|e| e.show(&self.filter)),
+            false => Ok(None),
+        };
+        self.filter_error = query.as_ref().err().cloned();
+
+        let mut rows: Vec<usize> = (0..snapins.len())
+            .filter(|&i| self.show_all || snapins[i].standalone)
+            .filter(|&i| match &query {
+                Ok(Some(query)) => query.matches(&snapins[i]),
+                Ok(None) | Err(_) => matches_filter(&snapins[i], &filter),
+            })
+            .collect();
+
+        rows.sort_by(|&a, &b| {
+            let order = compare_column(&snapins[a], &snapins[b], self.sort_column);
+            if self.sort_ascending { order } else { order.reverse() }
+        });
+
+        self.rows = rows;
+        self.selected = None;
+    }
+}
+
+fn matches_filter(snapin: &MMCSnapIn, filter: &str) -> bool {
+    if filter.is_empty() {
+        return true;
+    }
+
+    [
+        snapin.get_name(),
+        snapin.get_description(),
+        &snapin.clsid,
+        snapin.get_provider(),
+        snapin.module_name.as_deref().unwrap_or(""),
+    ]
+    .iter()
+    .any(|field| field.to_lowercase().contains(filter))
+}
+
+fn compare_column(a: &MMCSnapIn, b: &MMCSnapIn, column: Column) -> Ordering {
+    column.text(a).to_lowercase().cmp(&column.text(b).to_lowercase())
+}
+
+/// Everything known about a snap-in, one `label: value` per line with
+/// indented sections for the list-valued attributes.
+pub fn details_text(snapin: &MMCSnapIn) -> String {
+    let mut text = String::new();
+    let mut line = |label: &str, value: &str| {
+        if !value.is_empty() {
+            let _ = writeln!(text, "{}: {}", label, value);
+        }
+    };
+
+    line("Name", snapin.get_name());
+    line("CLSID", &snapin.clsid);
+    line("Description", snapin.get_description());
+    line("Standalone", if snapin.standalone { "Yes" } else { "No" });
+    line("Provider", snapin.get_provider());
+    line("Version", snapin.versionstringindirect.as_deref().unwrap_or(""));
+    line("Module", snapin.module_name.as_deref().unwrap_or(""));
+    line("Application base", snapin.application_base.as_deref().unwrap_or(""));
+    let restriction = &snapin.restriction;
+    line(if restriction.blocked { "Blocked by" } else { "Permitted by" }, restriction.policy.as_deref().unwrap_or(""));
+    line("Author mode restricted by", restriction.author_mode_policy.as_deref().unwrap_or(""));
+
+    if let Some(about) = &snapin.about {
+        line("About description", about.description.as_deref().unwrap_or(""));
+        line("About provider", about.provider.as_deref().unwrap_or(""));
+        line("About version", about.version.as_deref().unwrap_or(""));
+    }
+
+    let mut section = |title: &str, items: Vec<String>| {
+        if !items.is_empty() {
+            let _ = writeln!(text, "\n{}:", title);
+            for item in items {
+                let _ = writeln!(text, "    {}", item);
+            }
+        }
+    };
+
+    section("Registry values", snapin.registry_values.iter().map(|(n, d)| format!("{} = {}", n, d)).collect());
+    section("Node types", snapin.node_types.clone());
+    section("Extensions", snapin.extensions.iter().map(|e| {
+        format!("{} {} {} (on {})", e.kind, e.clsid, e.name.as_deref().unwrap_or(""), e.node_type)
+    }).collect());
+    section("In-proc servers", snapin.servers.iter().map(|server| {
+        let mut item = format!("{} {} ({})", server.clsid, server.path, server.root);
+        if server.is_user_override(&snapin.servers) {
+            item.push_str(", per-user override");
+        } else if !server.effective {
+            item.push_str(", shadowed");
+        }
+        item
+    }).collect());
+    section("Translations", snapin.translations.iter().flat_map(|(language, strings)| {
+        [("Name", &strings.name), ("Provider", &strings.provider), ("Version", &strings.version)]
+            .into_iter()
+            .filter_map(move |(label, s)| s.as_ref().map(|s| format!("{} {}: {}", language, label, s)))
+    }).collect());
+    section("Diagnostics", snapin.diagnostics.clone());
+
+    text
+}
+
+/// The node types a snap-in publishes and the snap-ins extending each, as an
+/// indented tree of node type, extension kind and extension. Extensions are
+/// named after the registered snap-in with their CLSID, if there is one.
+pub fn extension_tree(snapin: &MMCSnapIn, snapins: &[MMCSnapIn]) -> String {
+    let mut text = String::new();
+
+    for node_type in &snapin.node_types {
+        let _ = writeln!(text, "{}", node_type);
+
+        let mut extensions: Vec<&MMCExtension> = snapin.extensions
+            .iter()
+            .filter(|e| &e.node_type == node_type)
+            .collect();
+        extensions.sort();
+
+        let mut kind = None;
+        for extension in extensions {
+            if kind != Some(&extension.kind) {
+                let _ = writeln!(text, "  {}", extension.kind);
+                kind = Some(&extension.kind);
+            }
+
+            let clsid = normalize_clsid(&extension.clsid);
+            let name = snapins
+                .iter()
+                .find(|s| clsid.is_some() && normalize_clsid(&s.clsid) == clsid)
+                .map(|s| s.get_name())
+                .or(extension.name.as_deref())
+                .unwrap_or("");
+            let _ = writeln!(text, "    {}", format!("{} {}", extension.clsid, name).trim_end());
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_snapin(clsid: &str, name: &str, standalone: bool) -> MMCSnapIn {
+        MMCSnapIn {
+            clsid: clsid.into(),
+            namestring: Some(name.into()),
+            standalone,
+            ..Default::default()
+        }
+    }
+
+    fn test_model() -> SnapInListModel {
+        let mut dns = test_snapin("{2FAEBFA2-3F1A-11D0-8C65-00C04FD8FECB}", "DNS", true);
+        dns.module_name = Some(r"C:\Windows\System32\dnsmgr.dll".into());
+
+        SnapInListModel::new(vec![
+            test_snapin("{975797FC-4E2A-11D0-B702-00C04FD8DBF7}", "Event Viewer", true),
+            test_snapin("{58221C67-EA27-11CF-ADCF-00AA00A80033}", "Computer Management", true),
+            test_snapin("{394C052E-B830-11D0-9A86-00C04FD8DBF7}", "Event Log Extension", false),
+            dns,
+        ])
+    }
+
+    fn names(model: &SnapInListModel) -> Vec<&str> {
+        (0..model.rows().len()).map(|r| model.row(r).unwrap().get_name()).collect()
+    }
+
+    #[test]
+    fn test_rows_sorted_standalone_only() {
+        let model = test_model();
+
+        assert_eq!(names(&model), vec!["Computer Management", "DNS", "Event Viewer"]);
+    }
+
+    #[test]
+    fn test_filter_and_show_all() {
+        let mut model = test_model();
+        model.set_filter("EVENT");
+        assert_eq!(names(&model), vec!["Event Viewer"]);
+
+        model.set_show_all(true);
+        assert_eq!(names(&model), vec!["Event Log Extension", "Event Viewer"]);
+
+        model.set_filter("dnsmgr");
+        assert_eq!(names(&model), vec!["DNS"]);
+    }
+
+    #[test]
+    fn test_query_filter() {
+        let mut model = test_model();
+        model.set_filter(r#"name ~ "event" || module_name endswith dnsmgr.dll"#);
+        assert_eq!(names(&model), vec!["DNS", "Event Viewer"]);
+        assert!(model.filter_error().is_none());
+
+        model.set_filter("name ~");
+        assert!(names(&model).is_empty());
+        assert_eq!(model.filter_error(), Some("name ~\n      ^ expected a value after `~` at column 7"));
+
+        // Names that only look like queries are matched as text
+        let mut model = SnapInListModel::new(vec![
+            test_snapin("{53D6AB1D-2488-11D1-A28C-00C04FB94F17}", "Certificates (Local Computer)", true),
+            test_snapin("{11111111-2222-3333-4444-555555555555}", "AT&T Tools", true),
+        ]);
+        model.set_filter("Certificates (Local");
+        assert_eq!(names(&model), vec!["Certificates (Local Computer)"]);
+        assert!(model.filter_error().is_some());
+        model.set_filter("at&t");
+        assert_eq!(names(&model), vec!["AT&T Tools"]);
+    }
+
+    #[test]
+    fn test_sort_by_toggles_direction() {
+        let mut model = test_model();
+        model.sort_by(Column::Name);
+        assert_eq!(names(&model), vec!["Event Viewer", "DNS", "Computer Management"]);
+
+        model.sort_by(Column::Clsid);
+        assert_eq!(names(&model), vec!["DNS", "Computer Management", "Event Viewer"]);
+    }
+
+    #[test]
+    fn test_selection_cleared_on_filter() {
+        let mut model = test_model();
+        model.select(Some(1));
+        assert_eq!(model.selected().map(|s| s.get_name()), Some("DNS"));
+
+        model.set_filter("event");
+        assert!(model.selected().is_none());
+
+        model.select(Some(5));
+        assert!(model.selected().is_none());
+    }
+
+    #[test]
+    fn test_extension_tree() {
+        let node_type = "{476E6449-AAFF-11D0-B944-00C04FD8D5B0}".to_string();
+        let extension = |kind: &str, clsid: &str, name: Option<&str>| MMCExtension {
+            node_type: node_type.clone(),
+            kind: kind.into(),
+            clsid: clsid.into(),
+            name: name.map(str::to_string),
+        };
+
+        let mut compmgmt = test_snapin("{58221C67-EA27-11CF-ADCF-00AA00A80033}", "Computer Management", true);
+        compmgmt.node_types = vec![node_type.clone()];
+        compmgmt.extensions = vec![
+            extension("NameSpace", "{394c052e-b830-11d0-9a86-00c04fd8dbf7}", Some("Event Log")),
+            extension("ContextMenu", "{DEADBEEF-0000-0000-0000-000000000000}", None),
+        ];
+
+        let model = test_model();
+        assert_eq!(extension_tree(&compmgmt, model.snapins()), "\
+{476E6449-AAFF-11D0-B944-00C04FD8D5B0}
+  ContextMenu
+    {DEADBEEF-0000-0000-0000-000000000000}
+  NameSpace
+    {394c052e-b830-11d0-9a86-00c04fd8dbf7} Event Log Extension
+");
+    }
+
+    #[test]
+    fn test_icon_indices() {
+        let choices = [IconChoice::Image, IconChoice::Placeholder, IconChoice::Icon, IconChoice::Image];
+
+        assert_eq!(icon_indices(&choices), vec![1, 0, 2, 3]);
+        assert_eq!(test_model().icons(), &[IconChoice::Placeholder; 4]);
+    }
+
+    #[test]
+    fn test_update_keeps_selection() {
+        let mut model = test_model();
+        model.select(Some(1));
+        assert_eq!(model.selected().unwrap().get_name(), "DNS");
+
+        model.update(&["{58221C67-EA27-11CF-ADCF-00AA00A80033}".into()], vec![
+            test_snapin("{975797fc-4e2a-11d0-b702-00c04fd8dbf7}", "Event Viewer (Local)", true),
+            test_snapin("{11111111-2222-3333-4444-555555555555}", "Contoso", true),
+        ]);
+
+        assert_eq!(names(&model), vec!["Contoso", "DNS", "Event Viewer (Local)"]);
+        assert_eq!(model.snapins().len(), 4);
+        assert_eq!(model.selected().unwrap().get_name(), "DNS");
+    }
+}
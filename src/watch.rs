@@ -0,0 +1,294 @@
+use std::{collections::{BTreeMap, hash_map::DefaultHasher}, hash::{Hash, Hasher}, thread, time::Duration};
+
+use crate::diff::InventoryDiff;
+use crate::snapin::{inproc_servers, normalize_clsid, MMCRestriction, MMCSnapIn, Resolver, NODETYPES_KEY, SNAPINS_KEY};
+use crate::source::RegistrySource;
+
+/// What changed between two refreshes.
+pub struct Update {
+    pub diff: InventoryDiff,
+    /// The added and changed snap-ins, as read now.
+    // Only the Windows GUI shows them, the CLI just prints the diff
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub snapins: Vec<MMCSnapIn>,
+}
+
+/// Keeps track of the snap-ins registered in a source, re-reading only the
+/// ones whose registration changed. A registration is the snap-in's key and
+/// the extensions registered for its node types; changes to the modules it
+/// points at aren't noticed.
+pub struct Watcher<'a> {
+    resolver: &'a dyn Resolver,
+    languages: Vec<String>,
+    // By normalized CLSID: a hash of the registration, and the snap-in read
+    // from it without GDI handles
+    snapins: BTreeMap<String, (u64, MMCSnapIn)>,
+}
+
+impl<'a> Watcher<'a> {
+    /// Starts from `snapins`, already read from `source`.
+    pub fn new(source: &dyn RegistrySource, resolver: &'a dyn Resolver, languages: &[String], snapins: &[MMCSnapIn]) -> Self {
+        let snapins = snapins
+            .iter()
+            .filter_map(|s| Some((normalize_clsid(&s.clsid)?, (fingerprint(source, &s.clsid), s.text_only()))))
+            .collect();
+        Watcher { resolver, languages: languages.to_vec(), snapins }
+    }
+
+    /// Reads `source` again, returning what changed since the last time.
+    pub fn refresh(&mut self, source: &dyn RegistrySource) -> Update {
+        let mut old = Vec::new();
+        let mut new = Vec::new();
+        let mut seen = Vec::new();
+
+        for clsid in source.subkeys(SNAPINS_KEY).unwrap_or_default() {
+            let Some(key) = normalize_clsid(&clsid) else { continue };
+            seen.push(key.clone());
+
+            let hash = fingerprint(source, &clsid);
+            if self.snapins.get(&key).is_some_and(|(h, _)| *h == hash) {
+                continue;
+            }
+
+            match MMCSnapIn::read(source, self.resolver, &clsid, &self.languages) {
+                Ok(snapin) => {
+                    if let Some((_, previous)) = self.snapins.insert(key, (hash, snapin.text_only())) {
+                        old.push(previous);
+                    }
+                    new.push(snapin);
+                }
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+
+        let removed: Vec<String> = self.snapins.keys().filter(|k| !seen.contains(k)).cloned().collect();
+        for key in removed {
+            old.extend(self.snapins.remove(&key).map(|(_, s)| s));
+        }
+
+        Update { diff: InventoryDiff::new(&old, &new), snapins: new }
+    }
+}
+
+// Hashes everything `MMCSnapIn::read` looks at in the registry
+fn fingerprint(source: &dyn RegistrySource, clsid: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let key = format!("{}\\{}", SNAPINS_KEY, clsid);
+
+    for (name, data) in source.values(&key).unwrap_or_default() {
+        // Its classes can be registered again per user without touching it
+        if name.eq_ignore_ascii_case("About") {
+            inproc_servers(source, &data.to_string()).hash(&mut hasher);
+        }
+        (name, data.to_string()).hash(&mut hasher);
+    }
+    inproc_servers(source, clsid).hash(&mut hasher);
+    source.subkeys(&format!("{}\\StandAlone", key)).is_some().hash(&mut hasher);
+    for node_type in source.subkeys(&format!("{}\\NodeTypes", key)).unwrap_or_default() {
+        node_type.hash(&mut hasher);
+        let extensions = format!("{}\\{}\\Extensions", NODETYPES_KEY, node_type);
+        for kind in source.subkeys(&extensions).unwrap_or_default() {
+            kind.hash(&mut hasher);
+            for (name, data) in source.values(&format!("{}\\{}", extensions, kind)).unwrap_or_default() {
+                (name, data.to_string()).hash(&mut hasher);
+            }
+        }
+    }
+    // Group Policy can block or permit it without touching its registration
+    MMCRestriction::read(source, clsid).hash(&mut hasher);
+
+    hasher.finish()
+}
+
+/// Decides when to look for changes: when the registry says something under
+/// `MMC\SnapIns` or `MMC\NodeTypes` changed, or every `interval` otherwise.
+pub struct Trigger {
+    interval: Duration,
+    #[cfg(windows)]
+    notifier: Option<notify::RegistryNotifier>,
+}
+
+impl Trigger {
+    /// Looks every `interval`, for sources with no change notifications.
+    pub fn poll(interval: Duration) -> Self {
+        Trigger {
+            interval,
+            #[cfg(windows)]
+            notifier: None,
+        }
+    }
+
+    /// Waits for change notifications from the live registry, still looking
+    /// every `interval` in case one is missed.
+    #[cfg(windows)]
+    pub fn live(interval: Duration) -> Self {
+        let notifier = notify::RegistryNotifier::new()
+            .inspect_err(|e| log::warn!("No registry change notifications, polling every {:?}: {}", interval, e))
+            .ok();
+        Trigger { interval, notifier }
+    }
+
+    /// Returns when it's time to look again.
+    pub fn wait(&mut self) {
+        #[cfg(windows)]
+        if let Some(notifier) = &mut self.notifier {
+            notifier.wait(self.interval);
+            return;
+        }
+
+        thread::sleep(self.interval);
+    }
+}
+
+#[cfg(windows)]
+mod notify {
+    use std::{error::Error, time::Duration};
+
+    use windows::core::w;
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::System::Registry::{RegCloseKey, RegNotifyChangeKeyValue, RegOpenKeyExW, HKEY, HKEY_LOCAL_MACHINE, KEY_NOTIFY, REG_NOTIFY_CHANGE_LAST_SET, REG_NOTIFY_CHANGE_NAME};
+    use windows::Win32::System::Threading::{CreateEventW, WaitForMultipleObjects};
+
+    // One key and event per watched tree. Notifications are one-shot, so
+    // each is armed again after it fires.
+    pub struct RegistryNotifier {
+        keys: Vec<HKEY>,
+        events: Vec<HANDLE>,
+    }
+
+    impl RegistryNotifier {
+        pub fn new() -> Result<Self, Box<dyn Error>> {
+            let mut notifier = RegistryNotifier { keys: Vec::new(), events: Vec::new() };
+
+            for path in [w!(r"SOFTWARE\Microsoft\MMC\SnapIns"), w!(r"SOFTWARE\Microsoft\MMC\NodeTypes")] {
+                let mut key = HKEY::default();
+                unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, path, 0, KEY_NOTIFY, &mut key) }.ok()?;
+                notifier.keys.push(key);
+                notifier.events.push(unsafe { CreateEventW(None, false, false, None) }?);
+            }
+            for i in 0..notifier.keys.len() {
+                notifier.arm(i)?;
+            }
+
+            Ok(notifier)
+        }
+
+        fn arm(&self, i: usize) -> Result<(), Box<dyn Error>> {
+            let filter = REG_NOTIFY_CHANGE_NAME | REG_NOTIFY_CHANGE_LAST_SET;
+            unsafe { RegNotifyChangeKeyValue(self.keys[i], true, filter, self.events[i], true) }.ok()?;
+            Ok(())
+        }
+
+        /// Waits until either tree changes or `timeout` passes.
+        pub fn wait(&mut self, timeout: Duration) {
+            let result = unsafe { WaitForMultipleObjects(&self.events, false, timeout.as_millis().try_into().unwrap_or(u32::MAX)) };
+            let signalled = result.0 as usize;
+            if signalled < self.events.len() {
+                if let Err(e) = self.arm(signalled) {
+                    log::warn!("Couldn't watch the registry for changes again: {}", e);
+                }
+            }
+        }
+    }
+
+    impl Drop for RegistryNotifier {
+        fn drop(&mut self) {
+            for &key in &self.keys {
+                let _ = unsafe { RegCloseKey(key) };
+            }
+            for &event in &self.events {
+                let _ = unsafe { CloseHandle(event) };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapin::{get_snapins, NoResolver};
+    use crate::source::RegFile;
+
+    const BEFORE: &str = r#"Windows Registry Editor Version 5.00
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}]
+"NameString"="Computer Management"
+"About"="{58221C67-EA27-11CF-ADCF-00AA00A80033}"
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}\NodeTypes\{476E6449-AAFF-11D0-B944-00C04FD8D5B0}]
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{975797FC-4E2A-11D0-B702-00C04FD8DBF7}]
+"NameString"="Event Viewer"
+"About"="{975797FC-4E2A-11D0-B702-00C04FD8DBF7}"
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C6A-EA27-11CF-ADCF-00AA00A80033}]
+"NameString"="Services"
+"About"="{58221C6A-EA27-11CF-ADCF-00AA00A80033}"
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{394C052E-B830-11D0-9A86-00C04FD8DBF7}]
+"NameString"="Disk Management"
+"#;
+
+    // Event Viewer is renamed and extends Computer Management, which changes
+    // Computer Management too. Disk Management is gone, Contoso is new and
+    // Services is as it was.
+    const AFTER: &str = r#"Windows Registry Editor Version 5.00
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}]
+"NameString"="Computer Management"
+"About"="{58221C67-EA27-11CF-ADCF-00AA00A80033}"
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C67-EA27-11CF-ADCF-00AA00A80033}\NodeTypes\{476E6449-AAFF-11D0-B944-00C04FD8D5B0}]
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\NodeTypes\{476E6449-AAFF-11D0-B944-00C04FD8D5B0}\Extensions\NameSpace]
+"{975797FC-4E2A-11D0-B702-00C04FD8DBF7}"="Event Viewer"
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{975797FC-4E2A-11D0-B702-00C04FD8DBF7}]
+"NameString"="Event Viewer (Local)"
+"About"="{975797FC-4E2A-11D0-B702-00C04FD8DBF7}"
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{58221C6A-EA27-11CF-ADCF-00AA00A80033}]
+"NameString"="Services"
+"About"="{58221C6A-EA27-11CF-ADCF-00AA00A80033}"
+
+[HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\MMC\SnapIns\{11111111-2222-3333-4444-555555555555}]
+"NameString"="Contoso"
+"#;
+
+    // Counts what gets read, to check that unchanged snap-ins aren't
+    struct CountingResolver(std::sync::atomic::AtomicUsize);
+
+    impl Resolver for CountingResolver {
+        fn load_string(&self, _: &crate::nsi::IndirectString) -> Result<String, Box<dyn std::error::Error>> {
+            Err("not resolved".into())
+        }
+
+        fn about(&self, _: &str) -> Result<crate::snapin::MMCSnapInAbout, Box<dyn std::error::Error>> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Err("not resolved".into())
+        }
+    }
+
+    #[test]
+    fn test_refresh() {
+        let before = RegFile::parse(BEFORE).unwrap();
+        let snapins = get_snapins(&before, &NoResolver, 1, &[]).unwrap();
+        let resolver = CountingResolver(Default::default());
+        let mut watcher = Watcher::new(&before, &resolver, &[], &snapins);
+
+        assert!(watcher.refresh(&before).diff.is_empty());
+
+        let after = RegFile::parse(AFTER).unwrap();
+        let update = watcher.refresh(&after);
+
+        let names = |changes: &[crate::diff::SnapInChange]| changes.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&update.diff.added), vec!["Contoso"]);
+        assert_eq!(names(&update.diff.removed), vec!["Disk Management"]);
+        assert_eq!(names(&update.diff.modified), vec!["Computer Management", "Event Viewer (Local)"]);
+        assert_eq!(update.snapins.len(), 3);
+        // Only the changed snap-ins were read again
+        assert_eq!(resolver.0.load(std::sync::atomic::Ordering::Relaxed), 2);
+
+        assert!(watcher.refresh(&after).diff.is_empty());
+    }
+}
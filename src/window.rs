@@ -1,15 +1,32 @@
-use log::{debug, trace};
+use std::{cell::RefCell, error::Error, rc::Rc, sync::mpsc::{self, Receiver, Sender}, thread};
+
+use log::{debug, info, trace, warn};
 use windows::Win32::UI::WindowsAndMessaging::{LoadIconW, IDI_APPLICATION};
-use winsafe::{co::{ILC, LVS, LVSIL, SM}, gui, prelude::*, GetSystemMetricsForDpi, HIMAGELIST};
+use winsafe::{co::{ES, ILC, LVS, LVSIL, SM, SS, WS}, gui, msg, prelude::*, BmpIconCurMeta, GetSystemMetricsForDpi, HIMAGELIST};
 use winsafe::gui::{Horz, Vert};
 
+use crate::launch::{self, ProcessLauncher};
 use crate::snapin::MMCSnapIn;
+use crate::view::{self, Column, IconChoice, SnapInListModel};
+use crate::watch::Update;
+
+// How often the window picks up changes found by `watch`
+const WATCH_TIMER: usize = 1;
+const WATCH_TIMER_MS: u32 = 1000;
 
 #[derive(Clone)]
 pub struct MyWindow {
     pub wnd: gui::WindowMain,
     pub lv: gui::ListView<()>,
-    snapins: Vec<MMCSnapIn>,
+    filter: gui::Edit,
+    show_all: gui::CheckBox,
+    image: gui::Label,
+    details: gui::Edit,
+    model: Rc<RefCell<SnapInListModel>>,
+    // Image list index for each snap-in
+    icons: Rc<RefCell<Vec<u32>>>,
+    // Changes found by `watch`'s thread, waiting to be shown
+    updates: Rc<RefCell<Option<Receiver<Update>>>>,
 }
 
 impl MyWindow {
@@ -23,96 +40,282 @@ impl MyWindow {
             },
         );
 
-        let columns: Vec<(String, u32)> = vec![
-            ("Name".to_string(), 300),
-            ("Description".to_string(), 300),
-            ("CLSID".to_string(), 300)
-        ];
+        let filter = gui::Edit::new(
+            &wnd,
+            gui::EditOpts {
+                position: (10, 10),
+                width: 420,
+                resize_behavior: (Horz::Resize, Vert::None),
+                ..Default::default()
+            }
+        );
+
+        let show_all = gui::CheckBox::new(
+            &wnd,
+            gui::CheckBoxOpts {
+                text: "Show extension-only".to_owned(),
+                position: (445, 12),
+                resize_behavior: (Horz::Repos, Vert::None),
+                ..Default::default()
+            }
+        );
+
+        let columns: Vec<(String, u32)> = Column::ALL
+            .iter()
+            .map(|c| (c.title().to_string(), 300))
+            .collect();
         let lv = gui::ListView::new(
             &wnd,
             gui::ListViewOpts {
-                size: (640, 480),
+                position: (0, 40),
+                size: (640, 270),
                 columns,
-                list_view_style: LVS::SORTASCENDING | LVS::REPORT,
+                list_view_style: LVS::REPORT | LVS::SHOWSELALWAYS,
                 resize_behavior: (Horz::Resize, Vert::Resize),
                 ..Default::default()
             }
         );
 
-        let new_self = Self {wnd, lv, snapins};
+        // Details of the selected snap-in: its large image and a read-only
+        // text box with everything else we know about it
+        let image = gui::Label::new(
+            &wnd,
+            gui::LabelOpts {
+                position: (10, 320),
+                size: (48, 48),
+                label_style: SS::BITMAP | SS::CENTERIMAGE,
+                resize_behavior: (Horz::None, Vert::Repos),
+                ..Default::default()
+            }
+        );
+
+        let details = gui::Edit::new(
+            &wnd,
+            gui::EditOpts {
+                position: (68, 320),
+                width: 562,
+                height: 150,
+                edit_style: ES::MULTILINE | ES::READONLY | ES::AUTOVSCROLL,
+                window_style: WS::CHILD | WS::VISIBLE | WS::TABSTOP | WS::GROUP | WS::VSCROLL,
+                resize_behavior: (Horz::Resize, Vert::Repos),
+                ..Default::default()
+            }
+        );
+
+        let new_self = Self {
+            wnd,
+            lv,
+            filter,
+            show_all,
+            image,
+            details,
+            model: Rc::new(RefCell::new(SnapInListModel::new(snapins))),
+            icons: Rc::new(RefCell::new(Vec::new())),
+            updates: Rc::new(RefCell::new(None)),
+        };
         new_self.events();
         new_self
     }
 
-    fn events(&self) {
-        let self2 = self.clone();
-        self.wnd.on().wm_create(move |_| {
+    /// Runs `watch` on another thread, showing each update it sends.
+    pub fn watch<F>(&self, watch: F)
+        where F: FnOnce(Sender<Update>) -> Result<(), Box<dyn Error>> + Send + 'static
+    {
+        let (sender, receiver) = mpsc::channel();
+        *self.updates.borrow_mut() = Some(receiver);
+        thread::spawn(move || {
+            if let Err(e) = watch(sender) {
+                warn!("Stopped watching for changes: {}", e);
+            }
+        });
+    }
+
+    fn apply_updates(&self) {
+        let updates: Vec<Update> = match &*self.updates.borrow() {
+            Some(receiver) => receiver.try_iter().collect(),
+            None => return,
+        };
+        if updates.is_empty() {
+            return;
+        }
+
+        for update in updates {
+            info!("Registry changed:\n{}", update.diff);
+            let removed: Vec<String> = update.diff.removed.iter().map(|s| s.clsid.clone()).collect();
+            self.model.borrow_mut().update(&removed, update.snapins);
+        }
+        self.fill_image_list();
+        self.render_rows();
+    }
+
+    /// Rebuilds the image list from the model's snap-ins.
+    fn fill_image_list(&self) {
+        let dpi = self.wnd.hwnd().GetDpiForWindow();
+        let icon_cx = GetSystemMetricsForDpi(SM::CXICON, dpi).unwrap();
+        let icon_cy = GetSystemMetricsForDpi(SM::CYICON, dpi).unwrap();
+        debug!("DPI: {}, Icon size: {}, {}", dpi, icon_cx, icon_cy);
 
-            // Create imagelist
-            let dpi = self2.wnd.hwnd().GetDpiForWindow();
-            let icon_cx = GetSystemMetricsForDpi(SM::CXICON, dpi).unwrap();
-            let icon_cy = GetSystemMetricsForDpi(SM::CYICON, dpi).unwrap();
-            debug!("DPI: {}, Icon size: {}, {}", dpi, icon_cx, icon_cy);
-            
-            unsafe {
-                let small_il = HIMAGELIST::Create(
-                    winsafe::SIZE::new(
-                        icon_cx,
-                        icon_cy,
-                    ),
-                    ILC::MASK | ILC::COLOR32,
-                    self2.snapins.len() as i32,
-                    1
-                ).unwrap();
-
-                let mut count = 1;
-
-                // Load the placeholder icon (icon index 0)
-                let placeholder = LoadIconW(None, IDI_APPLICATION).unwrap();
-                let _ = small_il.AddIcon(&winsafe::HICON::from_ptr(placeholder.0 as *mut _));
-
-                for snapin in &self2.snapins {
-                    // Add snapin icon if it exists, else placeholder icon
-                    trace!("Adding snapin {}", &snapin.namestring.as_ref().unwrap_or(&"".to_string()));
-                    let icon_i: u32;
-                    if let Some(about) = &snapin.about {
+        let model = self.model.borrow();
+        *self.icons.borrow_mut() = view::icon_indices(model.icons());
+
+        unsafe {
+            let small_il = HIMAGELIST::Create(
+                winsafe::SIZE::new(
+                    icon_cx,
+                    icon_cy,
+                ),
+                ILC::MASK | ILC::COLOR32,
+                model.snapins().len() as i32,
+                1
+            ).unwrap();
+
+            // Load the placeholder icon (icon index 0)
+            let placeholder = LoadIconW(None, IDI_APPLICATION).unwrap();
+            let _ = small_il.AddIcon(&winsafe::HICON::from_ptr(placeholder.0 as *mut _));
+
+            // Add images and icons in snap-in order, which is the order
+            // view::icon_indices() numbered them in
+            for (snapin, choice) in model.snapins().iter().zip(model.icons()) {
+                trace!("Adding snapin {}: {:?}", snapin.get_name(), choice);
+                let Some(about) = &snapin.about else { continue };
+                match choice {
+                    IconChoice::Image => {
                         if let Some(image) = &about.image {
                             let _ = small_il.AddMasked(&winsafe::HBITMAP::from_ptr(image.large.0 as *mut _), winsafe::COLORREF::from_raw(image.mask.0));
-                            icon_i = count;
-                            count += 1;
-                            trace!("\tAdded image {},\t{:#08x}", icon_i, (image.mask.0 & 0xFFFFFF));
-                        } else if let Some(icon) = about.icon {
+                            trace!("\tAdded image,\t{:#08x}", (image.mask.0 & 0xFFFFFF));
+                        }
+                    }
+                    IconChoice::Icon => {
+                        if let Some(icon) = about.icon {
                             let _ = small_il.AddIcon(&winsafe::HICON::from_ptr(icon.0 as *mut _));
-                            icon_i = count;
-                            count += 1;
-                            trace!("\tAdded icon {}", icon_i);
-                        } else {
-                            icon_i = 0;
-                            trace!("\tUsed placeholder icon {}", icon_i);
                         }
-                    } else {
-                        icon_i = 0;
-                        trace!("\tUsed placeholder icon {}", icon_i);
                     }
+                    IconChoice::Placeholder => {}
+                }
+            }
+
+            // Dropping the image list this replaces destroys it
+            let _ = self.lv.set_image_list(LVSIL::SMALL, small_il);
+        }
+    }
 
+    /// Refills the list view from the model's rows.
+    fn render_rows(&self) {
+        // The list view sends notifications while it's being refilled, so
+        // don't hold the model borrowed here.
+        let rows: Vec<([String; 3], u32)> = {
+            let model = self.model.borrow();
+            model.rows()
+                .iter()
+                .map(|&i| {
+                    let snapin = &model.snapins()[i];
+                    (Column::ALL.map(|c| c.text(snapin).to_string()), self.icons.borrow()[i])
+                })
+                .collect()
+        };
 
-                    let snapin_name = snapin.get_name();
-                    let snapin_description = snapin.get_description();
+        self.lv.set_redraw(false);
+        self.lv.items().delete_all();
+        for (texts, icon) in rows {
+            self.lv.items().add(&texts, Some(icon), ());
+        }
+        self.lv.set_redraw(true);
 
-                    // Add the listview item to the view for the snapin.
-                    self2.lv.items().add(
-                        &[snapin_name, snapin_description, &snapin.clsid],
-                        Some(icon_i),
-                        ()
-                    );
-                }
+        self.render_details();
+    }
+
+    fn render_details(&self) {
+        let model = self.model.borrow();
+        let snapin = model.selected();
+
+        // Edit controls want CRLF line breaks. While the filter doesn't
+        // parse, say why instead.
+        let text = match model.filter_error() {
+            Some(error) => error.to_string(),
+            None => snapin.map(view::details_text).unwrap_or_default(),
+        };
+        self.details.set_text(&text.replace('\n', "\r\n"));
+
+        let bitmap = snapin
+            .and_then(|s| s.about.as_ref())
+            .and_then(|a| a.image.as_ref())
+            .map(|i| unsafe { winsafe::HBITMAP::from_ptr(i.large.0 as *mut _) })
+            .unwrap_or(winsafe::HBITMAP::NULL);
+
+        // The bitmap stays owned by the MMCSnapInImage, the label only borrows it
+        let _ = unsafe {
+            self.image.hwnd().SendMessage(msg::stm::SetImage { image: BmpIconCurMeta::Bmp(bitmap) })
+        };
+    }
 
-                self2.lv.set_image_list(LVSIL::SMALL, small_il);
+    fn events(&self) {
+        let self2 = self.clone();
+        self.wnd.on().wm_create(move |_| {
+            self2.fill_image_list();
+            self2.render_rows();
+
+            if self2.updates.borrow().is_some() {
+                self2.wnd.hwnd().SetTimer(WATCH_TIMER, WATCH_TIMER_MS, None)?;
             }
             Ok(0)
-        })
+        });
+
+        let self2 = self.clone();
+        self.wnd.on().wm_timer(WATCH_TIMER, move || {
+            self2.apply_updates();
+            Ok(())
+        });
+
+        let self2 = self.clone();
+        self.filter.on().en_change(move || {
+            self2.model.borrow_mut().set_filter(&self2.filter.text());
+            self2.render_rows();
+            Ok(())
+        });
 
+        let self2 = self.clone();
+        self.show_all.on().bn_clicked(move || {
+            self2.model.borrow_mut().set_show_all(self2.show_all.is_checked());
+            self2.render_rows();
+            Ok(())
+        });
+
+        let self2 = self.clone();
+        self.lv.on().lvn_item_changed(move |_| {
+            let selected = self2.lv.items().iter_selected().next().map(|item| item.index() as usize);
+            self2.model.borrow_mut().select(selected);
+            self2.render_details();
+            Ok(())
+        });
 
+        // Double-clicking or pressing Enter opens the selected snap-ins in a
+        // new console
+        let self2 = self.clone();
+        self.lv.on().lvn_item_activate(move |_| {
+            // Copies, since launching can pump messages and the watch timer
+            // may replace the model meanwhile
+            let snapins: Vec<MMCSnapIn> = {
+                let model = self2.model.borrow();
+                self2.lv.items()
+                    .iter_selected()
+                    .filter_map(|item| model.row(item.index() as usize).cloned())
+                    .collect()
+            };
+            match launch::open_in_mmc(&snapins.iter().collect::<Vec<_>>(), &ProcessLauncher) {
+                Ok(path) => info!("Opened {} in MMC", path.display()),
+                Err(e) => warn!("Couldn't open the snap-ins in MMC: {}", e),
+            }
+            Ok(())
+        });
 
+        // Clicking a header sorts by that column, clicking it again reverses
+        let self2 = self.clone();
+        self.lv.on().lvn_column_click(move |p| {
+            if let Some(&column) = Column::ALL.get(p.iSubItem as usize) {
+                self2.model.borrow_mut().sort_by(column);
+                self2.render_rows();
+            }
+            Ok(())
+        });
     }
-}
\ No newline at end of file
+}